@@ -0,0 +1,35 @@
+//! Reading activity over time, for keeping the addiction going.
+
+/// How many reading events happened on one (UTC) day.
+#[derive(Debug)]
+pub struct ActivityDay {
+    /// Days since the Unix epoch.
+    pub day: i64,
+    /// The day as `YYYY-MM-DD`.
+    pub date: String,
+    pub count: usize,
+}
+
+/// Computes the current and the longest streak of consecutive active days from `days`, which
+/// must be sorted by day. The current streak may end yesterday, since today isn't over yet.
+pub fn streaks(days: &[ActivityDay], today: i64) -> (usize, usize) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous = None;
+
+    for day in days {
+        run = match previous {
+            Some(p) if day.day == p + 1 => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(day.day);
+    }
+
+    let current = match previous {
+        Some(last) if last >= today - 1 => run,
+        _ => 0,
+    };
+
+    (current, longest)
+}
@@ -0,0 +1,154 @@
+//! Markdown chunking for the embedding pipeline.
+
+use anyhow::Result;
+use pulldown_cmark::{Event, Parser, TagEnd};
+use text_splitter::{ChunkConfig, MarkdownSplitter};
+
+/// Splits markdown documents into chunks for embedding.
+///
+/// [`MarkdownSplitter`] already prefers to split on headings over paragraphs and sentences, so a
+/// chunk rarely straddles two sections. On top of that, the chunker can prepend the nearest
+/// preceding heading to each chunk, so a chunk from the middle of a section still says where it
+/// came from. Only then is each chunk turned into plain text, which embeds better than markdown.
+pub struct Chunker {
+    splitter: MarkdownSplitter<text_splitter::Characters>,
+    heading_context: bool,
+}
+
+impl Chunker {
+    /// Creates a new [`Chunker`] with chunks of at most `capacity` characters, of which `overlap`
+    /// characters are shared with the previous chunk.
+    pub fn new(capacity: usize, overlap: usize, heading_context: bool) -> Result<Self> {
+        let config = ChunkConfig::new(capacity).with_overlap(overlap)?;
+
+        Ok(Self {
+            splitter: MarkdownSplitter::new(config),
+            heading_context,
+        })
+    }
+
+    /// Splits `markdown` into the plain text chunks we embed and store.
+    pub fn chunks(&self, markdown: &str) -> Vec<String> {
+        self.splitter
+            .chunk_indices(markdown)
+            .map(|(offset, chunk)| {
+                if !self.heading_context || is_heading(chunk.lines().next().unwrap_or_default()) {
+                    return to_plain_text(chunk);
+                }
+
+                match preceding_heading(&markdown[..offset]) {
+                    Some(heading) => to_plain_text(&format!("{heading}\n\n{chunk}")),
+                    None => to_plain_text(chunk),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Roughly estimates how many tokens `text` is, at about four bytes per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Strips the markup from `markdown`, keeping the text of headings, links and code, with blocks
+/// separated by blank lines.
+pub fn to_plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak | Event::End(TagEnd::Item | TagEnd::TableRow) => text.push('\n'),
+            Event::End(TagEnd::TableCell) => text.push('\t'),
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::CodeBlock
+                | TagEnd::BlockQuote(_)
+                | TagEnd::List(_)
+                | TagEnd::Table,
+            ) => {
+                let text_end = text.trim_end_matches('\n').len();
+                text.truncate(text_end);
+                text.push_str("\n\n");
+            }
+            _ => {}
+        }
+    }
+
+    text.trim().to_string()
+}
+
+/// Finds the last heading in `text`, skipping anything inside fenced code blocks.
+fn preceding_heading(text: &str) -> Option<&str> {
+    let mut in_fence = false;
+    let mut heading = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        } else if !in_fence && is_heading(line) {
+            heading = Some(line.trim());
+        }
+    }
+
+    heading
+}
+
+/// Whether `line` is an ATX heading, e.g. `## Section`.
+fn is_heading(line: &str) -> bool {
+    let hashes = line.chars().take_while(|c| *c == '#').count();
+
+    (1..=6).contains(&hashes) && line[hashes..].starts_with(' ')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepends_nearest_heading() {
+        let markdown = "\
+# Title
+
+Intro paragraph.
+
+## Second section
+
+First paragraph of the second section.
+
+Second paragraph of the second section.";
+
+        let chunker = Chunker::new(45, 0, true).unwrap();
+        let chunks = chunker.chunks(markdown);
+
+        assert_eq!(chunks.first().unwrap(), "Title\n\nIntro paragraph.");
+        assert_eq!(
+            chunks.last().unwrap(),
+            "Second section\n\nSecond paragraph of the second section."
+        );
+    }
+
+    #[test]
+    fn strips_markup() {
+        let markdown = "\
+## A *fine* [section](https://example.com)
+
+Some `code` and **bold**
+text\\.
+
+- one
+- two
+
+```
+let x = 1;
+```";
+
+        assert_eq!(
+            to_plain_text(markdown),
+            "A fine section\n\nSome code and bold text.\n\none\ntwo\n\nlet x = 1;"
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Turns clusters of similar articles into tags.
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Prefix of the tags we derive from clusters, so they can be told apart from (and removed without
+/// touching) the tags from Pocket.
+pub const TAG_PREFIX: &str = "cluster:";
+
+/// Clusters as the clustering scripts write them: cluster id to member URLs.
+pub type Clusters = BTreeMap<String, Vec<String>>;
+
+/// What we know about an item when labelling its cluster.
+#[derive(Debug)]
+pub struct ItemTags {
+    pub title: String,
+    pub tags: Vec<String>,
+}
+
+/// Words too common to say anything about a cluster.
+const STOPWORDS: &[&str] = &[
+    "about",
+    "after",
+    "also",
+    "been",
+    "being",
+    "between",
+    "could",
+    "does",
+    "from",
+    "have",
+    "here",
+    "into",
+    "just",
+    "like",
+    "more",
+    "most",
+    "much",
+    "over",
+    "some",
+    "than",
+    "that",
+    "their",
+    "them",
+    "then",
+    "there",
+    "these",
+    "they",
+    "this",
+    "what",
+    "when",
+    "where",
+    "which",
+    "while",
+    "will",
+    "with",
+    "would",
+    "your",
+    "wikipedia",
+];
+
+/// Picks a label for every cluster. Labels given in `labels` win, then the most frequent tag the
+/// members already have, and otherwise the most distinctive word of the members' titles (by
+/// TF-IDF, with each cluster's titles as one document).
+pub fn label_clusters(
+    clusters: &Clusters,
+    items: &HashMap<String, ItemTags>,
+    labels: &HashMap<String, String>,
+) -> BTreeMap<String, String> {
+    let keywords = top_keywords(clusters, items);
+
+    clusters
+        .iter()
+        .filter_map(|(id, members)| {
+            let label = labels
+                .get(id)
+                .cloned()
+                .or_else(|| most_frequent_tag(members, items))
+                .or_else(|| keywords.get(id).cloned())?;
+
+            Some((id.clone(), slugify(&label)))
+        })
+        .collect()
+}
+
+fn most_frequent_tag(members: &[String], items: &HashMap<String, ItemTags>) -> Option<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+
+    for tag in members
+        .iter()
+        .filter_map(|url| items.get(url))
+        .flat_map(|item| &item.tags)
+        .filter(|tag| !tag.starts_with(TAG_PREFIX))
+    {
+        *counts.entry(tag).or_default() += 1;
+    }
+
+    // Break ties alphabetically so the outcome doesn't depend on hash order.
+    counts
+        .into_iter()
+        .max_by(|(a, ca), (b, cb)| ca.cmp(cb).then(b.cmp(a)))
+        .map(|(tag, _)| tag.to_string())
+}
+
+fn top_keywords(clusters: &Clusters, items: &HashMap<String, ItemTags>) -> HashMap<String, String> {
+    let term_counts: BTreeMap<&String, HashMap<String, usize>> = clusters
+        .iter()
+        .map(|(id, members)| {
+            let mut counts = HashMap::new();
+            for word in members
+                .iter()
+                .filter_map(|url| items.get(url))
+                .flat_map(|item| words(&item.title))
+            {
+                *counts.entry(word).or_default() += 1;
+            }
+            (id, counts)
+        })
+        .collect();
+
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for counts in term_counts.values() {
+        for word in counts.keys() {
+            *document_frequency.entry(word).or_default() += 1;
+        }
+    }
+
+    let n = clusters.len() as f64;
+    term_counts
+        .iter()
+        .filter_map(|(id, counts)| {
+            counts
+                .iter()
+                .map(|(word, tf)| {
+                    let idf = (n / document_frequency[word.as_str()] as f64).ln() + 1.0;
+                    (word, *tf as f64 * idf)
+                })
+                .max_by(|(a, sa), (b, sb)| sa.total_cmp(sb).then(b.cmp(a)))
+                .map(|(word, _)| ((*id).clone(), word.clone()))
+        })
+        .collect()
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|w| w.chars().count() >= 4 && !STOPWORDS.contains(&w.as_str()))
+}
+
+/// Makes a label fit for a tag: lowercase, with anything that isn't a letter or digit (commas
+/// especially, since they separate tags) collapsed into dashes.
+pub fn slugify(label: &str) -> String {
+    label
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(title: &str, tags: &[&str]) -> ItemTags {
+        ItemTags {
+            title: title.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn labels() {
+        let clusters = Clusters::from([
+            ("0".to_string(), vec!["a".to_string(), "b".to_string()]),
+            ("1".to_string(), vec!["c".to_string(), "d".to_string()]),
+            ("2".to_string(), vec!["e".to_string()]),
+        ]);
+        let items = HashMap::from([
+            ("a".to_string(), item("Rust ownership", &["rust"])),
+            ("b".to_string(), item("Rust lifetimes", &[])),
+            ("c".to_string(), item("Sourdough bread", &[])),
+            ("d".to_string(), item("Baking sourdough at home", &[])),
+            ("e".to_string(), item("Whatever", &[])),
+        ]);
+        let labels = HashMap::from([("2".to_string(), "Cooking, Recipes & More".to_string())]);
+
+        let labelled = label_clusters(&clusters, &items, &labels);
+
+        assert_eq!(labelled["0"], "rust");
+        assert_eq!(labelled["1"], "sourdough");
+        assert_eq!(labelled["2"], "cooking-recipes-more");
+    }
+}
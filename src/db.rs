@@ -1,14 +1,81 @@
 //! Data store actor.
 
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+};
 
-use anyhow::Result;
-use reqwest::Url;
-use rusqlite::params;
+use anyhow::{Context, Result, bail};
+use ndarray::Array1;
+use rand::Rng;
+use regex::Regex;
+use reqwest::{StatusCode, Url};
+use rusqlite::{
+    OptionalExtension, functions::FunctionFlags, params, params_from_iter, types::Value,
+};
 use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
 use tokio_rusqlite::Connection;
 
-use crate::{pocket::PocketItem, worker::CrawledArticle};
+use crate::{
+    activity::ActivityDay,
+    clusters::{Clusters, ItemTags},
+    digest::{DigestItem, DigestStrategy, WORDS_PER_MINUTE, reading_minutes},
+    embedding::{DEFAULT_MODEL, cosine_similarity},
+    feed::{FEED_LEN, FeedItem, SUMMARY_LEN},
+    offline::OfflineArticle,
+    opml::ListItem,
+    pocket::{PocketItem, PocketStatus},
+    pooling::normalize,
+    urls::canonicalize,
+    worker::{CrawlError, CrawledArticle, HeadCheck, Link, RawPage, is_html_content_type},
+};
+
+/// Columns added after the initial schema, as `(table, column, definition)`. SQLite has no
+/// `ADD COLUMN IF NOT EXISTS`, so [`Db::new`] adds whichever of these are missing.
+const MIGRATIONS: &[(&str, &str, &str)] = &[
+    ("items", "content_type", "TEXT"),
+    ("items", "content_length", "INTEGER"),
+    ("items", "link_dead", "INTEGER"),
+    ("items", "plain_text", "TEXT"),
+    ("items", "skip_reason", "TEXT"),
+    ("items", "published_at", "INTEGER"),
+    ("items", "final_url", "TEXT"),
+    ("items", "status_set_locally", "INTEGER"),
+    ("items", "time_embedded", "INTEGER"),
+    ("items", "cluster_id", "INTEGER"),
+    ("items", "author", "TEXT"),
+    ("items", "raw_html", "TEXT"),
+    ("items", "paywalled", "INTEGER"),
+    ("items", "pub_id", "TEXT"),
+    ("items", "doc_vector_dims", "INTEGER"),
+    ("items", "embedding_model", "TEXT"),
+    ("chunks", "model", "TEXT"),
+];
+
+/// Columns of `items` that hold crawl results rather than Pocket metadata.
+const CONTENT_COLUMNS: &[&str] = &[
+    "time_last_crawl",
+    "http_status_last_crawl",
+    "html",
+    "raw_html",
+    "markdown",
+    "plain_text",
+    "published_at",
+    "author",
+    "paywalled",
+    "doc_vector",
+    "doc_vector_dims",
+    "embedding_model",
+    "content_type",
+    "content_length",
+    "final_url",
+    "time_embedded",
+];
+
+/// How many rows [`Db::stream_all_items`] reads ahead of the receiver.
+const STREAM_BUFFER: usize = 64;
 
 /// Data store backed by SQLite.
 pub struct Db {
@@ -18,9 +85,11 @@ pub struct Db {
 impl Db {
     pub async fn new(db_path: PathBuf) -> Result<Self> {
         let conn = Connection::open(db_path).await?;
+        let tuning = tuning_pragmas()?;
 
         // I guess we're doing our migrations in line now with rusqlite?
-        conn.call(|conn| {
+        conn.call(move |conn| {
+            conn.execute_batch(&tuning)?;
             conn.execute_batch(
                 "PRAGMA journal_mode = WAL;
                 PRAGMA synchronous = NORMAL;
@@ -36,56 +105,261 @@ impl Db {
                    markdown TEXT,
                    doc_vector BLOB
                 );
+                CREATE TABLE IF NOT EXISTS read_events (
+                    id INTEGER PRIMARY KEY,
+                    item_url TEXT NOT NULL,
+                    kind TEXT NOT NULL,
+                    at INTEGER NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS links (
+                    item_url TEXT NOT NULL,
+                    href TEXT NOT NULL,
+                    text TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS links_item_url ON links (item_url);
+                CREATE TABLE IF NOT EXISTS crawl_errors (
+                    id INTEGER PRIMARY KEY,
+                    url TEXT NOT NULL,
+                    at INTEGER NOT NULL,
+                    message TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS versions (
+                    id INTEGER PRIMARY KEY,
+                    url TEXT NOT NULL,
+                    time_crawl INTEGER,
+                    content_hash TEXT NOT NULL,
+                    markdown TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS versions_url ON versions (url);
                 CREATE TABLE IF NOT EXISTS chunks (
                     id INTEGER PRIMARY KEY,
                     url TEXT NOT NULL,
                     chunk TEXT NOT NULL,
                     vector BLOB NOT NULL
                 );",
-            )
+            )?;
+
+            // Backs the `REGEXP` operator, which SQLite leaves up to the application.
+            conn.create_scalar_function(
+                "regexp",
+                2,
+                FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+                |ctx| {
+                    let re = ctx.get_or_create_aux(0, |pattern| {
+                        Regex::new(pattern.as_str()?)
+                            .map_err(|e| rusqlite::Error::UserFunctionError(e.into()))
+                    })?;
+                    let text = ctx.get::<String>(1)?;
+
+                    Ok(re.is_match(&text))
+                },
+            )?;
+
+            conn.create_scalar_function(
+                "is_html_content_type",
+                1,
+                FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+                |ctx| Ok(is_html_content_type(&ctx.get::<String>(0)?)),
+            )?;
+
+            // Full-text index over the title and markdown of crawled items, filled with whatever
+            // we already crawled when it's first created.
+            let has_fts: bool = conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'items_fts')",
+                [],
+                |row| row.get(0),
+            )?;
+            if !has_fts {
+                conn.execute_batch(
+                    "CREATE VIRTUAL TABLE items_fts USING fts5(url UNINDEXED, title, markdown);",
+                )?;
+                reindex_all(conn)?;
+            }
+
+            for (table, column, definition) in MIGRATIONS {
+                if !table_columns(conn, "main", table)?
+                    .iter()
+                    .any(|c| c == column)
+                {
+                    conn.execute(
+                        &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+                        [],
+                    )?;
+                }
+            }
+
+            conn.execute_batch(
+                "CREATE UNIQUE INDEX IF NOT EXISTS items_pub_id ON items (pub_id);",
+            )?;
+            backfill_pub_ids(conn)?;
+
+            // Everything embedded before we kept track of models was embedded with the default.
+            conn.execute(
+                "UPDATE items SET embedding_model = ?
+                WHERE doc_vector IS NOT NULL AND embedding_model IS NULL",
+                params![DEFAULT_MODEL],
+            )?;
+            conn.execute(
+                "UPDATE chunks SET model = ? WHERE model IS NULL",
+                params![DEFAULT_MODEL],
+            )?;
+
+            Ok::<_, rusqlite::Error>(())
         })
         .await?;
 
         Ok(Self { conn })
     }
 
-    pub async fn save_item(&self, item: PocketItem) -> Result<()> {
-        let _ = self
+    /// Inserts or updates an item from a Pocket export. Returns whether this archived an item we
+    /// already had as unread, which is also recorded as a read event.
+    ///
+    /// Re-importing an export overwrites an item's title and tags with Pocket's, and its status
+    /// too unless we changed that ourselves with [`Db::set_status`]: the export is older than
+    /// whatever we did here, so our status wins.
+    pub async fn save_item(&self, item: PocketItem) -> Result<bool> {
+        let archived = self
             .conn
             .call(move |conn| {
-                conn.execute(
-                    "INSERT INTO items (url, title, time_added, tags, status)
-                    VALUES (?1, ?2, ?3, ?4, ?5)
+                let tx = conn.transaction()?;
+
+                let previous: Option<(String, bool)> = tx
+                    .query_row(
+                        "SELECT status, coalesce(status_set_locally, 0) FROM items WHERE url = ?",
+                        params![item.url.to_string()],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()?;
+
+                tx.execute(
+                    "INSERT INTO items (url, title, time_added, tags, status, pub_id)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
                     ON CONFLICT(url) DO UPDATE SET
                         title=excluded.title,
                         tags=excluded.tags,
-                        status=excluded.status",
+                        status=CASE WHEN coalesce(status_set_locally, 0)
+                            THEN status ELSE excluded.status END",
                     params![
                         item.url.to_string(),
                         item.title,
                         item.time_added,
                         item.tags.to_string(),
                         item.status.to_string(),
+                        new_pub_id(),
                     ],
+                )?;
+
+                // The title may have changed.
+                index_item(&tx, item.url.as_str())?;
+
+                let archived = matches!(item.status, PocketStatus::Archive)
+                    && previous.is_some_and(|(status, set_locally)| {
+                        !set_locally && status != PocketStatus::Archive.to_string()
+                    });
+                if archived {
+                    tx.execute(
+                        "INSERT INTO read_events (item_url, kind, at) VALUES (?, 'archive', unixepoch())",
+                        params![item.url.to_string()],
+                    )?;
+                }
+
+                tx.commit()?;
+
+                Ok::<_, rusqlite::Error>(archived)
+            })
+            .await?;
+
+        Ok(archived)
+    }
+
+    pub async fn get_all_urls(&self) -> Result<HashSet<String>> {
+        let urls = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare("SELECT url FROM items")?;
+                stmt.query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<HashSet<String>>>()
+            })
+            .await?;
+
+        Ok(urls)
+    }
+
+    /// Sets an item's status, recording a read event when that archives it. Returns whether the
+    /// status changed, or `None` if we don't know the item.
+    pub async fn set_status(&self, url: Url, status: PocketStatus) -> Result<Option<bool>> {
+        let changed = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let url = url.to_string();
+
+                let previous: Option<String> = tx
+                    .query_row(
+                        "SELECT status FROM items WHERE url = ?",
+                        params![url],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let Some(previous) = previous else {
+                    return Ok(None);
+                };
+                let changed = previous != status.to_string();
+
+                tx.execute(
+                    "UPDATE items SET status = ?, status_set_locally = 1 WHERE url = ?",
+                    params![status.to_string(), url],
+                )?;
+
+                if matches!(status, PocketStatus::Archive) && changed {
+                    tx.execute(
+                        "INSERT INTO read_events (item_url, kind, at) VALUES (?, 'archive', unixepoch())",
+                        params![url],
+                    )?;
+                }
+
+                tx.commit()?;
+
+                Ok::<_, rusqlite::Error>(Some(changed))
+            })
+            .await?;
+
+        Ok(changed)
+    }
+
+    /// Adds a URL to the reading list as a new unread item. Returns whether it was new.
+    pub async fn save_url(&self, url: Url, title: String) -> Result<bool> {
+        let inserted = self
+            .conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT OR IGNORE INTO items (url, title, time_added, tags, status, pub_id)
+                    VALUES (?, ?, unixepoch(), '', 'unread', ?)",
+                    params![url.to_string(), title, new_pub_id()],
                 )
             })
             .await?;
 
-        Ok(())
+        Ok(inserted > 0)
     }
 
-    pub async fn get_uncrawled_items(&self, limit: Option<usize>) -> Result<Vec<ItemHandle>> {
+    /// Gets crawled items that are worth another try: the crawl got an error status, or so little
+    /// markdown that it's more likely a cookie wall or error page than the article.
+    pub async fn get_recrawlable_items(&self, filter: CandidateFilter) -> Result<Vec<ItemHandle>> {
         let items: Vec<String> = self
             .conn
             .call(move |conn| {
-                let sql = match limit {
-                    Some(n) => format!("SELECT url FROM items WHERE html IS NULL LIMIT {n}"),
-                    None => "SELECT url FROM items WHERE html IS NULL".to_string(),
-                };
+                let (sql, values) = filter.apply(&format!(
+                    "SELECT url FROM items
+                    WHERE coalesce(html, raw_html) IS NOT NULL AND skip_reason IS NULL
+                    AND (http_status_last_crawl >= 400
+                        OR length(coalesce(markdown, '')) < {SHORT_MARKDOWN_CHARS})"
+                ));
 
                 let mut stmt = conn.prepare(&sql)?;
 
-                stmt.query_map([], |row| row.get(0))?.collect()
+                stmt.query_map(params_from_iter(values), |row| row.get(0))?
+                    .collect()
             })
             .await?;
 
@@ -98,21 +372,166 @@ impl Db {
         Ok(items)
     }
 
-    pub async fn save_crawl(&self, crawl: CrawledArticle) -> Result<()> {
-        let _ = self
+    /// Gets pages crawled with `--no-extract` that haven't been through Readability yet.
+    pub async fn get_unextracted_items(&self, filter: CandidateFilter) -> Result<Vec<RawPage>> {
+        // Raw crawls used to go in `html`, before we had `raw_html`.
+        self.get_raw_pages(
+            filter,
+            "coalesce(raw_html, html) IS NOT NULL AND markdown IS NULL",
+        )
+        .await
+    }
+
+    /// Gets all pages we have the fetched HTML of, to run Readability over again.
+    pub async fn get_reparsable_items(&self, filter: CandidateFilter) -> Result<Vec<RawPage>> {
+        self.get_raw_pages(filter, "raw_html IS NOT NULL").await
+    }
+
+    async fn get_raw_pages(
+        &self,
+        filter: CandidateFilter,
+        condition: &'static str,
+    ) -> Result<Vec<RawPage>> {
+        let rows: Vec<(String, String, u64, u16, String)> = self
+            .conn
+            .call(move |conn| {
+                let (sql, values) = filter.apply(&format!(
+                    "SELECT url, coalesce(final_url, url), coalesce(time_last_crawl, 0),
+                        coalesce(http_status_last_crawl, 200), coalesce(raw_html, html)
+                    FROM items WHERE {condition}"
+                ));
+
+                let mut stmt = conn.prepare(&sql)?;
+
+                stmt.query_map(params_from_iter(values), |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                })?
+                .collect()
+            })
+            .await?;
+
+        let pages = rows
+            .into_iter()
+            .filter_map(|(url, final_url, timestamp, status, html)| {
+                Some(RawPage {
+                    timestamp,
+                    status: StatusCode::from_u16(status).ok()?,
+                    url: Url::parse(&url).ok()?,
+                    final_url: Url::parse(&final_url).ok()?,
+                    html,
+                })
+            })
+            .collect();
+
+        Ok(pages)
+    }
+
+    pub async fn get_uncrawled_items(&self, filter: CandidateFilter) -> Result<Vec<ItemHandle>> {
+        let items: Vec<String> = self
+            .conn
+            .call(move |conn| {
+                // Skip items a `--head-only` pass found to be dead or not HTML. Some servers
+                // refuse HEAD or rate limit us, so those statuses don't count as dead.
+                let (sql, values) = filter.apply(
+                    "SELECT url FROM items
+                    WHERE html IS NULL AND raw_html IS NULL AND skip_reason IS NULL
+                    AND NOT (coalesce(http_status_last_crawl, 0) BETWEEN 400 AND 499
+                        AND http_status_last_crawl NOT IN (405, 408, 429))
+                    AND (content_type IS NULL OR is_html_content_type(content_type))",
+                );
+
+                let mut stmt = conn.prepare(&sql)?;
+
+                stmt.query_map(params_from_iter(values), |row| row.get(0))?
+                    .collect()
+            })
+            .await?;
+
+        let items = items
+            .iter()
+            .filter_map(|s| Url::parse(s).ok())
+            .map(|url| ItemHandle { url })
+            .collect();
+
+        Ok(items)
+    }
+
+    /// Marks items we can't crawl because their URL isn't http(s), e.g. `file://` or `about:`, so
+    /// they stop showing up as crawl candidates. Returns how many were newly marked.
+    pub async fn skip_unsupported_schemes(&self) -> Result<usize> {
+        let skipped = self
             .conn
             .call(move |conn| {
                 conn.execute(
-                    "UPDATE items
-                    SET time_last_crawl = ?, http_status_last_crawl = ?, html = ?, markdown = ?
-                    WHERE url = ?",
-                    params![
-                        crawl.timestamp,
-                        crawl.status.as_u16(),
-                        crawl.html,
-                        crawl.markdown,
-                        crawl.url.to_string()
-                    ],
+                    "UPDATE items SET skip_reason = 'unsupported scheme'
+                    WHERE skip_reason IS NULL
+                    AND substr(url, 1, instr(url, ':') - 1) NOT IN ('http', 'https')",
+                    [],
+                )
+            })
+            .await?;
+
+        Ok(skipped)
+    }
+
+    /// Saves the results of crawling an item, if `policy` allows replacing what we have. Returns
+    /// whether it was saved.
+    ///
+    /// If the markdown changed since the last crawl, the new markdown is also kept as a version,
+    /// up to [`MAX_VERSIONS`] per item. The extracted title replaces the item's title only if
+    /// that's empty or just the URL.
+    pub async fn save_crawl(&self, crawl: CrawledArticle, policy: OverwritePolicy) -> Result<bool> {
+        let saved = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let saved = save_crawl(&tx, crawl, policy)?;
+                tx.commit()?;
+
+                Ok::<_, rusqlite::Error>(saved)
+            })
+            .await?;
+
+        Ok(saved)
+    }
+
+    /// Saves the results of crawling several items like [`Db::save_crawl`], all in one
+    /// transaction. Returns whether each was saved, in order.
+    pub async fn save_crawls(
+        &self,
+        crawls: Vec<CrawledArticle>,
+        policy: OverwritePolicy,
+    ) -> Result<Vec<bool>> {
+        let saved = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let saved = crawls
+                    .into_iter()
+                    .map(|crawl| save_crawl(&tx, crawl, policy))
+                    .collect::<rusqlite::Result<_>>()?;
+                tx.commit()?;
+
+                Ok::<_, rusqlite::Error>(saved)
+            })
+            .await?;
+
+        Ok(saved)
+    }
+
+    /// Marks an item as one we won't crawl, e.g. because robots.txt asks us not to.
+    pub async fn save_skip_reason(&self, url: Url, reason: &'static str) -> Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE items SET skip_reason = ? WHERE url = ?",
+                    params![reason, url.to_string()],
                 )
             })
             .await?;
@@ -120,66 +539,179 @@ impl Db {
         Ok(())
     }
 
-    pub async fn get_crawl_status_hist(&self) -> Result<HashMap<Option<u16>, usize>> {
-        let status_codes: Vec<Option<u16>> = self
+    /// Records that crawling an item failed, so we can look into it after the run.
+    pub async fn save_crawl_error(&self, err: CrawlError) -> Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO crawl_errors (url, at, message) VALUES (?, unixepoch(), ?)",
+                    params![err.url.to_string(), err.message],
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Gets the `n` most recent crawl failures, newest first.
+    pub async fn get_crawl_errors(&self, n: usize) -> Result<Vec<CrawlErrorRecord>> {
+        let errors = self
             .conn
             .call(move |conn| {
-                let mut stmt = conn.prepare("SELECT http_status_last_crawl FROM items")?;
-                stmt.query_map([], |row| row.get::<_, Option<u16>>(0))?
-                    .collect()
+                let mut stmt = conn.prepare(
+                    "SELECT url, at, message FROM crawl_errors ORDER BY id DESC LIMIT ?",
+                )?;
+                stmt.query_map(params![n], |row| {
+                    Ok(CrawlErrorRecord {
+                        url: row.get(0)?,
+                        at: row.get(1)?,
+                        message: row.get(2)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
             })
             .await?;
 
-        let mut hist = HashMap::new();
+        Ok(errors)
+    }
 
-        for code in status_codes {
-            *hist.entry(code).or_insert(0) += 1;
-        }
+    /// Saves a fetched page without extracted content, for extracting later. Returns false if we
+    /// don't know the item.
+    pub async fn save_raw_crawl(&self, page: RawPage) -> Result<bool> {
+        let updated = self
+            .conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE items
+                    SET time_last_crawl = ?, http_status_last_crawl = ?, raw_html = ?, final_url = ?
+                    WHERE url = ?",
+                    params![
+                        page.timestamp,
+                        page.status.as_u16(),
+                        page.html,
+                        page.final_url.to_string(),
+                        page.url.to_string()
+                    ],
+                )
+            })
+            .await?;
 
-        Ok(hist)
+        Ok(updated > 0)
     }
 
-    pub async fn get_unembedded_items(&self, limit: Option<usize>) -> Result<Vec<ItemForChunking>> {
-        let items: Vec<(String, String)> = self
+    /// Finds crawled items matching an FTS5 `query` on their title and markdown, best matches
+    /// first.
+    pub async fn search(&self, query: String, limit: usize) -> Result<Vec<SearchHit>> {
+        let hits = self
             .conn
             .call(move |conn| {
-                let sql = match limit {
-                    Some(n) => format!("SELECT url, markdown FROM items WHERE doc_vector IS NULL AND markdown IS NOT NULL LIMIT {n}"),
-                    None => "SELECT url, markdown FROM items WHERE doc_vector IS NULL AND markdown IS NOT NULL".to_string(),
-                };
+                let mut stmt = conn.prepare(
+                    "SELECT url, title, snippet(items_fts, 2, '**', '**', '…', 16)
+                    FROM items_fts WHERE items_fts MATCH ?
+                    ORDER BY rank LIMIT ?",
+                )?;
+                stmt.query_map(params![query, limit], |row| {
+                    Ok(SearchHit {
+                        url: row.get(0)?,
+                        title: row.get(1)?,
+                        snippet: row.get(2)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await?;
 
-                let mut stmt = conn.prepare(&sql)?;
+        Ok(hits)
+    }
 
-                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect()
+    /// Gets what we know about an item, apart from its content, by its URL or public id.
+    pub async fn get_item_meta(&self, url_or_id: String) -> Result<Option<ItemMeta>> {
+        let meta = self
+            .conn
+            .call(move |conn| {
+                conn.query_row(
+                    "SELECT url, title, status, time_added, time_last_crawl, http_status_last_crawl,
+                        final_url, author, published_at, coalesce(paywalled, 0), pub_id,
+                        coalesce(tags, '')
+                    FROM items WHERE url = ?1 OR pub_id = ?1",
+                    params![url_or_id],
+                    |row| {
+                        Ok(ItemMeta {
+                            url: row.get(0)?,
+                            title: row.get(1)?,
+                            status: row.get(2)?,
+                            time_added: row.get(3)?,
+                            time_last_crawl: row.get(4)?,
+                            http_status: row.get(5)?,
+                            final_url: row.get(6)?,
+                            author: row.get(7)?,
+                            published_at: row.get(8)?,
+                            paywalled: row.get(9)?,
+                            pub_id: row.get(10)?,
+                            tags: split_tags(&row.get::<_, String>(11)?),
+                        })
+                    },
+                )
+                .optional()
             })
             .await?;
 
-        let items = items
-            .into_iter()
-            .filter_map(|(u, markdown)| {
-                Url::parse(&u)
-                    .ok()
-                    .map(|url| ItemForChunking { url, markdown })
+        Ok(meta)
+    }
+
+    /// Gets the outbound links found in an item when it was last crawled.
+    pub async fn get_links(&self, url: Url) -> Result<Vec<Link>> {
+        let links = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare("SELECT href, text FROM links WHERE item_url = ?")?;
+                stmt.query_map(params![url.to_string()], |row| {
+                    Ok(Link {
+                        href: row.get(0)?,
+                        text: row.get(1)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
             })
-            .collect();
+            .await?;
 
-        Ok(items)
+        Ok(links)
     }
 
-    pub async fn save_chunk_and_embedding(
-        &self,
-        url: Url,
-        chunk: String,
-        vector: &[f32],
-    ) -> Result<()> {
-        let bytes: Vec<u8> = vector.iter().flat_map(|f| f.to_le_bytes()).collect();
+    /// Gets the markdown of the last two distinct crawls of an item, oldest first.
+    pub async fn get_last_two_versions(&self, url: Url) -> Result<Option<(String, String)>> {
+        let versions: Vec<String> = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT markdown FROM versions WHERE url = ? ORDER BY id DESC LIMIT 2",
+                )?;
+                stmt.query_map(params![url.to_string()], |row| row.get(0))?
+                    .collect()
+            })
+            .await?;
 
+        let mut versions = versions.into_iter();
+        Ok(match (versions.next(), versions.next()) {
+            (Some(new), Some(old)) => Some((old, new)),
+            _ => None,
+        })
+    }
+
+    pub async fn save_head_check(&self, check: HeadCheck) -> Result<()> {
         let _ = self
             .conn
             .call(move |conn| {
                 conn.execute(
-                    "INSERT INTO chunks (url, chunk, vector) VALUES (?1, ?2, ?3)",
-                    params![url.to_string(), chunk, bytes],
+                    "UPDATE items
+                    SET http_status_last_crawl = ?, content_type = ?, content_length = ?
+                    WHERE url = ?",
+                    params![
+                        check.status.as_u16(),
+                        check.content_type,
+                        check.content_length,
+                        check.url.to_string()
+                    ],
                 )
             })
             .await?;
@@ -187,17 +719,35 @@ impl Db {
         Ok(())
     }
 
-    pub async fn save_doc_vector(&self, url: Url, doc_vector: &[f32]) -> Result<()> {
-        let bytes: Vec<u8> = doc_vector.iter().flat_map(|f| f.to_le_bytes()).collect();
+    pub async fn get_archived_items(&self, filter: CandidateFilter) -> Result<Vec<ItemHandle>> {
+        let items: Vec<String> = self
+            .conn
+            .call(move |conn| {
+                let (sql, values) = filter.apply("SELECT url FROM items WHERE status = 'archive'");
+
+                let mut stmt = conn.prepare(&sql)?;
+
+                stmt.query_map(params_from_iter(values), |row| row.get(0))?
+                    .collect()
+            })
+            .await?;
+
+        let items = items
+            .iter()
+            .filter_map(|s| Url::parse(s).ok())
+            .map(|url| ItemHandle { url })
+            .collect();
+
+        Ok(items)
+    }
 
+    pub async fn save_link_dead(&self, url: Url, dead: bool) -> Result<()> {
         let _ = self
             .conn
             .call(move |conn| {
                 conn.execute(
-                    "UPDATE items
-                    SET doc_vector = ?
-                    WHERE url = ?",
-                    params![bytes, url.to_string()],
+                    "UPDATE items SET link_dead = ? WHERE url = ?",
+                    params![dead, url.to_string()],
                 )
             })
             .await?;
@@ -205,44 +755,1920 @@ impl Db {
         Ok(())
     }
 
-    pub async fn get_urls_with_doc_vector(&self) -> Result<Vec<UrlWithDocVector>> {
-        let items: Vec<(String, Vec<u8>)> = self
+    pub async fn get_crawl_status_hist(
+        &self,
+        filter: HistogramFilter,
+    ) -> Result<HashMap<Option<u16>, usize>> {
+        let status_codes: Vec<Option<u16>> = self
             .conn
             .call(move |conn| {
-                let mut stmt = conn.prepare("SELECT url, doc_vector FROM items WHERE markdown IS NOT NULL AND doc_vector IS NOT NULL")?;
-                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect()
+                let (sql, values) =
+                    filter.apply("SELECT http_status_last_crawl FROM items WHERE 1 = 1");
+
+                let mut stmt = conn.prepare(&sql)?;
+                stmt.query_map(params_from_iter(values), |row| row.get::<_, Option<u16>>(0))?
+                    .collect()
             })
             .await?;
 
-        let items = items
-            .into_iter()
-            .map(|(url, vector)| {
-                let vector = vector
-                    .chunks_exact(4)
-                    .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
-                    .collect();
+        let mut hist = HashMap::new();
+
+        for code in status_codes {
+            *hist.entry(code).or_insert(0) += 1;
+        }
+
+        Ok(hist)
+    }
 
-                UrlWithDocVector { url, vector }
+    /// Counts the items `filter` lets through per reading status.
+    pub async fn count_by_status(
+        &self,
+        filter: HistogramFilter,
+    ) -> Result<BTreeMap<String, usize>> {
+        let counts = self
+            .conn
+            .call(move |conn| {
+                let (sql, values) = filter.apply("SELECT status, COUNT(*) FROM items WHERE 1 = 1");
+                let mut stmt = conn.prepare(&format!("{sql} GROUP BY status"))?;
+                stmt.query_map(params_from_iter(values), |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?
+                .collect::<rusqlite::Result<BTreeMap<_, _>>>()
             })
-            .collect();
+            .await?;
 
-        Ok(items)
+        Ok(counts)
     }
-}
 
-#[derive(Debug)]
-pub struct ItemHandle {
-    pub url: Url,
-}
+    /// Gets aggregate numbers about the items in the library that `filter` lets through.
+    pub async fn get_library_stats(&self, filter: HistogramFilter) -> Result<LibraryStats> {
+        let by_status = self.count_by_status(filter.clone()).await?;
 
-#[derive(Debug)]
-pub struct ItemForChunking {
-    pub url: Url,
-    pub markdown: String,
-}
+        let stats = self
+            .conn
+            .call(move |conn| {
+                let (sql, values) = filter.apply(
+                    "SELECT COUNT(*),
+                        COUNT(markdown),
+                        COUNT(doc_vector),
+                        COUNT(http_status_last_crawl),
+                        COUNT(CASE WHEN http_status_last_crawl BETWEEN 200 AND 299 THEN 1 END),
+                        COUNT(CASE WHEN paywalled THEN 1 END)
+                    FROM items WHERE 1 = 1",
+                );
+                let (total, with_markdown, embedded, crawled, crawled_ok, paywalled): (
+                    usize,
+                    usize,
+                    usize,
+                    usize,
+                    usize,
+                    usize,
+                ) = conn.query_row(&sql, params_from_iter(values), |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ))
+                })?;
 
-#[derive(Debug, Serialize)]
-pub struct UrlWithDocVector {
-    pub url: String,
-    pub vector: Vec<f32>,
+                // SQLite can't count words, so we do it here.
+                let (sql, values) =
+                    filter.apply("SELECT markdown FROM items WHERE markdown IS NOT NULL");
+                let mut stmt = conn.prepare(&sql)?;
+                let mut rows = stmt.query(params_from_iter(values))?;
+                let mut total_words = 0;
+                while let Some(row) = rows.next()? {
+                    total_words += row.get::<_, String>(0)?.split_whitespace().count();
+                }
+
+                Ok::<_, rusqlite::Error>(LibraryStats {
+                    total,
+                    by_status,
+                    with_markdown,
+                    embedded,
+                    total_words,
+                    avg_reading_minutes: (with_markdown > 0).then(|| {
+                        total_words as f64 / WORDS_PER_MINUTE as f64 / with_markdown as f64
+                    }),
+                    crawled,
+                    paywalled,
+                    crawl_success_rate: (crawled > 0).then(|| crawled_ok as f64 / crawled as f64),
+                })
+            })
+            .await?;
+
+        Ok(stats)
+    }
+
+    pub async fn get_unembedded_items(
+        &self,
+        filter: CandidateFilter,
+    ) -> Result<Vec<ItemForChunking>> {
+        let items: Vec<(String, String)> = self
+            .conn
+            .call(move |conn| {
+                let (sql, values) = filter.apply(
+                    "SELECT url, markdown FROM items
+                    WHERE doc_vector IS NULL AND markdown IS NOT NULL",
+                );
+
+                let mut stmt = conn.prepare(&sql)?;
+
+                stmt.query_map(params_from_iter(values), |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?
+                .collect()
+            })
+            .await?;
+
+        let items = items
+            .into_iter()
+            .filter_map(|(u, markdown)| {
+                Url::parse(&u)
+                    .ok()
+                    .map(|url| ItemForChunking { url, markdown })
+            })
+            .collect();
+
+        Ok(items)
+    }
+
+    /// Saves an item's chunks with their embeddings and its document embedding, replacing those
+    /// of an earlier embedding in the same transaction, so a failed reembed leaves the old ones.
+    pub async fn save_embeddings(
+        &self,
+        url: Url,
+        chunks: Vec<(String, Vec<f32>)>,
+        doc_vector: &[f32],
+        model: String,
+    ) -> Result<()> {
+        let doc_bytes = encode_vector(doc_vector);
+        let dims = doc_vector.len();
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let url = url.to_string();
+
+                tx.execute("DELETE FROM chunks WHERE url = ?", params![url])?;
+                {
+                    let mut insert = tx.prepare(
+                        "INSERT INTO chunks (url, chunk, vector, model) VALUES (?1, ?2, ?3, ?4)",
+                    )?;
+                    for (chunk, vector) in chunks {
+                        insert.execute(params![url, chunk, encode_vector(&vector), model])?;
+                    }
+                }
+                tx.execute(
+                    "UPDATE items
+                    SET doc_vector = ?, doc_vector_dims = ?, embedding_model = ?,
+                        time_embedded = unixepoch()
+                    WHERE url = ?",
+                    params![doc_bytes, dims, model, url],
+                )?;
+
+                tx.commit()
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Gets the markdown to embed of specific items, whether they were embedded before or not.
+    pub async fn get_items_for_chunking(&self, urls: Vec<Url>) -> Result<Vec<ItemForChunking>> {
+        let items = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT markdown FROM items
+                    WHERE url = ? AND markdown IS NOT NULL",
+                )?;
+
+                let mut items = vec![];
+                for url in urls {
+                    let markdown = stmt
+                        .query_row(params![url.to_string()], |row| row.get(0))
+                        .optional()?;
+                    if let Some(markdown) = markdown {
+                        items.push(ItemForChunking { url, markdown });
+                    }
+                }
+
+                Ok::<_, rusqlite::Error>(items)
+            })
+            .await?;
+
+        Ok(items)
+    }
+
+    /// Gets items that were crawled again after they were embedded, so their embeddings may no
+    /// longer match their text. Items embedded before we kept track of when aren't included.
+    pub async fn get_stale_embeddings(&self) -> Result<Vec<Url>> {
+        let urls: Vec<String> = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT url FROM items
+                    WHERE doc_vector IS NOT NULL AND time_last_crawl > time_embedded",
+                )?;
+
+                stmt.query_map([], |row| row.get(0))?.collect()
+            })
+            .await?;
+
+        Ok(urls.iter().filter_map(|u| Url::parse(u).ok()).collect())
+    }
+
+    pub async fn save_doc_vector(&self, url: Url, doc_vector: &[f32], model: String) -> Result<()> {
+        let bytes = encode_vector(doc_vector);
+        let dims = doc_vector.len();
+
+        let _ = self
+            .conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE items
+                    SET doc_vector = ?, doc_vector_dims = ?, embedding_model = ?,
+                        time_embedded = unixepoch()
+                    WHERE url = ?",
+                    params![bytes, dims, model, url.to_string()],
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Scales all stored doc vectors to unit length, e.g. after embedding some with and some
+    /// without `--normalize`. Returns how many weren't already.
+    pub async fn normalize_doc_vectors(&self) -> Result<usize> {
+        let rows: Vec<(String, StoredVector)> = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT url, doc_vector, doc_vector_dims, embedding_model
+                    FROM items WHERE doc_vector IS NOT NULL",
+                )?;
+                stmt.query_map([], |row| {
+                    Ok((row.get(0)?, (row.get(1)?, row.get(2)?, row.get(3)?)))
+                })?
+                .collect()
+            })
+            .await?;
+
+        let mut updates = vec![];
+        for (url, (vector, dims, _)) in rows {
+            let vector = Array1::from_vec(
+                decode_vector(&vector, dims)
+                    .with_context(|| format!("bad doc vector for {url}"))?,
+            );
+            let normalized = normalize(&vector);
+            if normalized
+                .iter()
+                .zip(&vector)
+                .any(|(a, b)| (a - b).abs() > 1e-6)
+            {
+                updates.push((url, encode_vector(&normalized.to_vec())));
+            }
+        }
+
+        let count = updates.len();
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                for (url, bytes) in updates {
+                    tx.execute(
+                        "UPDATE items SET doc_vector = ? WHERE url = ?",
+                        params![bytes, url],
+                    )?;
+                }
+                tx.commit()
+            })
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Stores which cluster each item is in, replacing any earlier clustering.
+    pub async fn save_cluster_assignments(&self, assignments: Vec<(String, usize)>) -> Result<()> {
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+
+                tx.execute("UPDATE items SET cluster_id = NULL", [])?;
+                for (url, cluster) in assignments {
+                    tx.execute(
+                        "UPDATE items SET cluster_id = ? WHERE url = ?",
+                        params![cluster, url],
+                    )?;
+                }
+
+                tx.commit()
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Gets the clusters stored by [`Db::save_cluster_assignments`].
+    pub async fn get_clusters(&self) -> Result<Clusters> {
+        let rows: Vec<(usize, String)> = self
+            .conn
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare("SELECT cluster_id, url FROM items WHERE cluster_id IS NOT NULL")?;
+
+                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect()
+            })
+            .await?;
+
+        let mut clusters = Clusters::new();
+        for (cluster, url) in rows {
+            clusters.entry(cluster.to_string()).or_default().push(url);
+        }
+
+        Ok(clusters)
+    }
+
+    /// Finds the `n` items whose doc vectors are most similar to `url`'s, leaving out those less
+    /// similar than `min_similarity`. Returns nothing if `url` has no doc vector yet.
+    pub async fn get_similar(
+        &self,
+        url: Url,
+        n: usize,
+        min_similarity: f32,
+    ) -> Result<Vec<SimilarItem>> {
+        let vectors = self.get_doc_vectors().await?;
+
+        let url = url.to_string();
+        let Some(target) = vectors.iter().find(|v| v.url == url) else {
+            return Ok(vec![]);
+        };
+
+        let mut similar = rank_by_similarity(&vectors, &target.vector, target.model.as_deref());
+        similar.retain(|item| item.url != url && item.similarity >= min_similarity);
+        similar.truncate(n);
+
+        Ok(similar)
+    }
+
+    /// Finds the `n` items whose doc vectors are most similar to `query`, an embedding of a
+    /// search query by `model`.
+    pub async fn get_nearest(
+        &self,
+        query: Vec<f32>,
+        model: String,
+        n: usize,
+    ) -> Result<Vec<SimilarItem>> {
+        let vectors = self.get_doc_vectors().await?;
+
+        let mut nearest = rank_by_similarity(&vectors, &query, Some(&model));
+        nearest.truncate(n);
+
+        Ok(nearest)
+    }
+
+    /// Counts the items embedded with `model`, and the items with markdown that could be.
+    pub async fn count_embedded(&self, model: String) -> Result<(usize, usize)> {
+        let counts = self
+            .conn
+            .call(move |conn| {
+                conn.query_row(
+                    "SELECT
+                        count(CASE WHEN doc_vector IS NOT NULL AND embedding_model = ? THEN 1 END),
+                        count(markdown)
+                    FROM items",
+                    params![model],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+            })
+            .await?;
+
+        Ok(counts)
+    }
+
+    async fn get_doc_vectors(&self) -> Result<Vec<DocVector>> {
+        let rows: Vec<(String, String, StoredVector)> = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT url, title, doc_vector, doc_vector_dims, embedding_model
+                    FROM items WHERE doc_vector IS NOT NULL",
+                )?;
+                stmt.query_map([], |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        (row.get(2)?, row.get(3)?, row.get(4)?),
+                    ))
+                })?
+                .collect()
+            })
+            .await?;
+
+        rows.into_iter()
+            .map(|(url, title, (vector, dims, model))| {
+                let vector = decode_vector(&vector, dims)
+                    .with_context(|| format!("bad doc vector for {url}"))?;
+                Ok(DocVector {
+                    url,
+                    title,
+                    vector,
+                    model,
+                })
+            })
+            .collect()
+    }
+
+    /// Gets the doc vectors of all embedded items, or only of those embedded with `model`.
+    pub async fn get_urls_with_doc_vector(
+        &self,
+        model: Option<String>,
+    ) -> Result<Vec<UrlWithDocVector>> {
+        let items: Vec<(String, StoredVector)> = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT url, doc_vector, doc_vector_dims, embedding_model FROM items
+                    WHERE markdown IS NOT NULL AND doc_vector IS NOT NULL
+                    AND (?1 IS NULL OR embedding_model = ?1)",
+                )?;
+                stmt.query_map(params![model], |row| {
+                    Ok((row.get(0)?, (row.get(1)?, row.get(2)?, row.get(3)?)))
+                })?
+                .collect()
+            })
+            .await?;
+
+        let items = items
+            .into_iter()
+            .map(|(url, (vector, dims, model))| {
+                let vector = decode_vector(&vector, dims)
+                    .with_context(|| format!("bad doc vector for {url}"))?;
+                Ok(UrlWithDocVector { url, vector, model })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(items)
+    }
+
+    /// Gets the unread items we have readable content for, in the order `strategy` asks for.
+    pub async fn get_digest_candidates(&self, strategy: DigestStrategy) -> Result<Vec<DigestItem>> {
+        let items: Vec<(String, String, String)> = self
+            .conn
+            .call(move |conn| {
+                let order = strategy.order_by();
+
+                // Only items that crawled fine, so we don't hand out error pages.
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT url, title, markdown FROM items
+                    WHERE status = 'unread' AND markdown IS NOT NULL AND markdown != ''
+                    AND coalesce(http_status_last_crawl, 200) < 400
+                    ORDER BY {order}"
+                ))?;
+                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                    .collect()
+            })
+            .await?;
+
+        let items = items
+            .into_iter()
+            .map(|(url, title, markdown)| DigestItem {
+                url,
+                title,
+                minutes: reading_minutes(&markdown),
+            })
+            .collect();
+
+        Ok(items)
+    }
+
+    /// Counts read events per day, oldest first.
+    pub async fn get_activity(&self) -> Result<Vec<ActivityDay>> {
+        let days = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT at / 86400 AS day, date(at, 'unixepoch'), COUNT(*) FROM read_events
+                    GROUP BY day ORDER BY day",
+                )?;
+                stmt.query_map([], |row| {
+                    Ok(ActivityDay {
+                        day: row.get(0)?,
+                        date: row.get(1)?,
+                        count: row.get(2)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await?;
+
+        Ok(days)
+    }
+
+    /// Gets the URLs of the next `n` unread items, in the order `strategy` asks for. Items we
+    /// know we can't open are left out.
+    pub async fn get_unread_queue(
+        &self,
+        n: usize,
+        strategy: DigestStrategy,
+    ) -> Result<Vec<String>> {
+        let urls = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT url FROM items
+                    WHERE status = 'unread' AND skip_reason IS NULL AND coalesce(link_dead, 0) = 0
+                    ORDER BY {} LIMIT {n}",
+                    strategy.order_by()
+                ))?;
+                stmt.query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .await?;
+
+        Ok(urls)
+    }
+
+    /// Gets the items on the reading list that `filter` lets through, oldest first.
+    pub async fn get_list_items(&self, filter: ExportFilter) -> Result<Vec<ListItem>> {
+        let items = self
+            .conn
+            .call(move |conn| {
+                let (sql, values) = filter.apply(
+                    "SELECT url, title, coalesce(tags, ''), time_added FROM items WHERE 1 = 1",
+                );
+                let mut stmt = conn.prepare(&format!("{sql} ORDER BY time_added"))?;
+                stmt.query_map(params_from_iter(values), |row| {
+                    Ok(ListItem {
+                        url: row.get(0)?,
+                        title: row.get(1)?,
+                        tags: split_tags(&row.get::<_, String>(2)?),
+                        time_added: row.get(3)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await?;
+
+        Ok(items)
+    }
+
+    /// Gets the [`FEED_LEN`] most recently saved items that `filter` lets through, newest first,
+    /// with the start of their markdown to summarize them.
+    pub async fn get_feed_items(&self, filter: ExportFilter) -> Result<Vec<FeedItem>> {
+        let items = self
+            .conn
+            .call(move |conn| {
+                // Twice the summary, as whitespace gets collapsed before cutting it.
+                let (sql, values) = filter.apply(&format!(
+                    "SELECT url, title, time_added, substr(markdown, 1, {}) FROM items WHERE 1 = 1",
+                    2 * SUMMARY_LEN
+                ));
+                let mut stmt =
+                    conn.prepare(&format!("{sql} ORDER BY time_added DESC LIMIT {FEED_LEN}"))?;
+                stmt.query_map(params_from_iter(values), |row| {
+                    Ok(FeedItem {
+                        url: row.get(0)?,
+                        title: row.get(1)?,
+                        time_added: row.get(2)?,
+                        markdown: row.get(3)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await?;
+
+        Ok(items)
+    }
+
+    /// Streams every item that `filter` lets through with its content, oldest first. Rows are read
+    /// as the receiver takes them, so the library never has to fit in memory.
+    pub fn stream_all_items(&self, filter: ExportFilter) -> mpsc::Receiver<Result<ExportedItem>> {
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        let conn = self.conn.clone();
+
+        tokio::spawn(async move {
+            let err_tx = tx.clone();
+            let streamed = conn
+                .call(move |conn| {
+                    let (sql, values) = filter.apply(
+                        "SELECT url, title, coalesce(tags, ''), status, time_added, markdown,
+                            doc_vector, doc_vector_dims
+                        FROM items
+                        WHERE 1 = 1",
+                    );
+                    let mut stmt = conn.prepare(&format!("{sql} ORDER BY time_added"))?;
+                    let mut rows = stmt.query(params_from_iter(values))?;
+
+                    while let Some(row) = rows.next()? {
+                        // The receiver hung up, nobody wants the rest.
+                        if tx.blocking_send(exported_item(row)).is_err() {
+                            break;
+                        }
+                    }
+
+                    Ok::<_, rusqlite::Error>(())
+                })
+                .await;
+
+            if let Err(err) = streamed {
+                let _ = err_tx.send(Err(err.into())).await;
+            }
+        });
+
+        rx
+    }
+
+    /// Gets every item we have extracted an article for, or only those saved at or after `since`,
+    /// oldest first.
+    pub async fn get_offline_articles(&self, since: Option<i64>) -> Result<Vec<OfflineArticle>> {
+        let articles = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT url, title, author, published_at, time_added, html FROM items
+                    WHERE markdown IS NOT NULL AND html IS NOT NULL
+                        AND (?1 IS NULL OR time_added >= ?1)
+                    ORDER BY time_added",
+                )?;
+                stmt.query_map(params![since], |row| {
+                    Ok(OfflineArticle {
+                        url: row.get(0)?,
+                        title: row.get(1)?,
+                        author: row.get(2)?,
+                        published_at: row.get(3)?,
+                        time_added: row.get(4)?,
+                        html: row.get(5)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await?;
+
+        Ok(articles)
+    }
+
+    /// Counts the items with each tag, most used tags first.
+    pub async fn get_tag_counts(&self) -> Result<Vec<(String, usize)>> {
+        let tags: Vec<String> = self
+            .conn
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare("SELECT tags FROM items WHERE coalesce(tags, '') != ''")?;
+                stmt.query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await?;
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for tag in tags.iter().flat_map(|tags| split_tags(tags)) {
+            *counts.entry(tag).or_default() += 1;
+        }
+
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+
+        Ok(counts)
+    }
+
+    /// Gets the items tagged `tag`, oldest first.
+    pub async fn get_items_by_tag(&self, tag: String) -> Result<Vec<ListItem>> {
+        // Tags are stored joined by commas, so `LIKE` narrows it down and we match exactly here.
+        let pattern = format!("%{tag}%");
+        let items = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT url, title, tags, time_added FROM items
+                    WHERE tags LIKE ? ORDER BY time_added",
+                )?;
+                stmt.query_map(params![pattern], |row| {
+                    Ok(ListItem {
+                        url: row.get(0)?,
+                        title: row.get(1)?,
+                        tags: split_tags(&row.get::<_, String>(2)?),
+                        time_added: row.get(3)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await?;
+
+        Ok(items
+            .into_iter()
+            .filter(|item| item.tags.contains(&tag))
+            .collect())
+    }
+
+    /// Gets the title and tags of every item, by URL.
+    pub async fn get_item_tags(&self) -> Result<HashMap<String, ItemTags>> {
+        let items: Vec<(String, String, Option<String>)> = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare("SELECT url, title, tags FROM items")?;
+                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                    .collect()
+            })
+            .await?;
+
+        let items = items
+            .into_iter()
+            .map(|(url, title, tags)| {
+                let tags = split_tags(tags.as_deref().unwrap_or_default());
+                (url, ItemTags { title, tags })
+            })
+            .collect();
+
+        Ok(items)
+    }
+
+    /// Sets the tag of each `(url, tag)` pair on items that have no tags yet. Returns how many
+    /// items got tagged.
+    pub async fn tag_untagged(&self, tags: Vec<(String, String)>) -> Result<usize> {
+        let tagged = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+
+                let mut tagged = 0;
+                for (url, tag) in tags {
+                    tagged += tx.execute(
+                        "UPDATE items SET tags = ? WHERE url = ? AND coalesce(tags, '') = ''",
+                        params![tag, url],
+                    )?;
+                }
+
+                tx.commit()?;
+
+                Ok::<_, rusqlite::Error>(tagged)
+            })
+            .await?;
+
+        Ok(tagged)
+    }
+
+    /// Removes every tag starting with `prefix`. Returns how many items had one.
+    pub async fn remove_tags_with_prefix(&self, prefix: &'static str) -> Result<usize> {
+        let removed = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+
+                let items: Vec<(String, String)> = tx
+                    .prepare("SELECT url, tags FROM items WHERE instr(tags, ?) > 0")?
+                    .query_map(params![prefix], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<rusqlite::Result<_>>()?;
+
+                let mut removed = 0;
+                for (url, tags) in items {
+                    let kept = split_tags(&tags)
+                        .into_iter()
+                        .filter(|tag| !tag.starts_with(prefix))
+                        .collect::<Vec<_>>()
+                        .join(",");
+
+                    if kept != tags {
+                        tx.execute(
+                            "UPDATE items SET tags = ? WHERE url = ?",
+                            params![kept, url],
+                        )?;
+                        removed += 1;
+                    }
+                }
+
+                tx.commit()?;
+
+                Ok::<_, rusqlite::Error>(removed)
+            })
+            .await?;
+
+        Ok(removed)
+    }
+
+    /// Merges the items and chunks of another database into this one.
+    ///
+    /// Items only present in `other` are added as-is. For items present in both, the crawl and
+    /// embedding columns are taken from `other` when its row is richer: it has a doc vector where
+    /// ours doesn't, or else longer markdown, or else a more recent crawl. The Pocket metadata
+    /// (title, tags, status) of our own row is kept. The chunks of the rows we take from `other`
+    /// come along with them, replacing ours for the rows it updates.
+    pub async fn merge(&self, other: PathBuf) -> Result<MergeReport> {
+        if !other.exists() {
+            bail!("no database found at {}", other.display());
+        }
+
+        let other = other.to_string_lossy().into_owned();
+
+        let report = self
+            .conn
+            .call(move |conn| {
+                // ATTACH can't run inside a transaction, so do it up front.
+                conn.execute("ATTACH DATABASE ?1 AS other", params![other])?;
+                let merged = merge_attached(conn);
+                // Detach even when the merge failed, or `other` stays attached to our connection.
+                let detached = conn.execute("DETACH DATABASE other", []);
+                let report = merged?;
+                detached?;
+
+                Ok::<_, rusqlite::Error>(report)
+            })
+            .await?;
+
+        Ok(report)
+    }
+
+    /// Merges items whose URLs only differ in ways that [`canonicalize`] irons out, like a
+    /// trailing slash or tracking parameters. Of each group we keep the row that has markdown, or
+    /// else the oldest one, and give it the group's earliest `time_added`, all of its tags, and
+    /// `archive` status if any of them was archived. Read events move over to the kept row, and
+    /// the content of the others goes. Returns how many rows were merged away.
+    pub async fn merge_duplicates(&self) -> Result<usize> {
+        let merged =
+            self.conn
+                .call(move |conn| {
+                    let tx = conn.transaction()?;
+
+                    let rows: Vec<(String, i64, String, String, bool)> = tx
+                    .prepare(
+                        "SELECT url, time_added, status, coalesce(tags, ''), markdown IS NOT NULL
+                        FROM items",
+                    )?
+                    .query_map([], |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+                    })?
+                    .collect::<rusqlite::Result<_>>()?;
+
+                    let mut groups: HashMap<String, Vec<_>> = HashMap::new();
+                    for row in rows {
+                        let key =
+                            Url::parse(&row.0).map_or(row.0.clone(), |url| canonicalize(&url));
+                        groups.entry(key).or_default().push(row);
+                    }
+
+                    let mut merged = 0;
+                    for mut group in groups.into_values().filter(|g| g.len() > 1) {
+                        group.sort_by_key(|(_, time_added, _, _, has_markdown)| {
+                            (!has_markdown, *time_added)
+                        });
+                        let (keep, ..) = &group[0];
+
+                        let time_added = group.iter().map(|(_, t, ..)| *t).min();
+                        let archived = group.iter().any(|(_, _, status, ..)| status == "archive");
+                        let mut tags = vec![];
+                        for (.., row_tags, _) in &group {
+                            for tag in split_tags(row_tags) {
+                                if !tags.contains(&tag) {
+                                    tags.push(tag);
+                                }
+                            }
+                        }
+
+                        tx.execute(
+                            "UPDATE items
+                        SET time_added = ?, tags = ?,
+                            status = CASE WHEN ? THEN 'archive' ELSE status END
+                        WHERE url = ?",
+                            params![time_added, tags.join(","), archived, keep],
+                        )?;
+
+                        for (url, ..) in &group[1..] {
+                            tx.execute(
+                                "UPDATE read_events SET item_url = ? WHERE item_url = ?",
+                                params![keep, url],
+                            )?;
+                            tx.execute("DELETE FROM links WHERE item_url = ?", params![url])?;
+                            tx.execute("DELETE FROM versions WHERE url = ?", params![url])?;
+                            tx.execute("DELETE FROM chunks WHERE url = ?", params![url])?;
+                            tx.execute("DELETE FROM items_fts WHERE url = ?", params![url])?;
+                            tx.execute("DELETE FROM items WHERE url = ?", params![url])?;
+                            merged += 1;
+                        }
+                    }
+
+                    tx.commit()?;
+
+                    Ok::<_, rusqlite::Error>(merged)
+                })
+                .await?;
+
+        Ok(merged)
+    }
+
+    /// Compacts and tidies up the database file: merges the full-text index's segments, rewrites
+    /// the file without the pages deleted rows left behind, refreshes the query planner's
+    /// statistics and truncates the write-ahead log.
+    pub async fn maintenance(&self) -> Result<()> {
+        self.conn
+            .call(|conn| {
+                conn.execute_batch(
+                    "INSERT INTO items_fts (items_fts) VALUES ('optimize');
+                    VACUUM;
+                    ANALYZE;
+                    PRAGMA optimize;",
+                )?;
+                // VACUUM goes through the log like any other write, so it's the last to go.
+                conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Builds the performance pragmas asked for through the environment, for big libraries on big
+/// machines:
+///
+/// - `SQLITE_CACHE_SIZE`: page cache size, in pages or in KiB if negative (`PRAGMA cache_size`)
+/// - `SQLITE_MMAP_SIZE`: how many bytes of the file to memory map (`PRAGMA mmap_size`)
+/// - `SQLITE_TEMP_STORE`: `default`, `file` or `memory` (`PRAGMA temp_store`)
+///
+/// SQLite's defaults apply to whatever isn't set.
+fn tuning_pragmas() -> Result<String> {
+    let mut pragmas = String::new();
+
+    if let Ok(size) = std::env::var("SQLITE_CACHE_SIZE") {
+        let size: i64 = size
+            .parse()
+            .context("SQLITE_CACHE_SIZE must be an integer")?;
+        pragmas.push_str(&format!("PRAGMA cache_size = {size};"));
+    }
+
+    if let Ok(size) = std::env::var("SQLITE_MMAP_SIZE") {
+        let size: u64 = size
+            .parse()
+            .context("SQLITE_MMAP_SIZE must be a number of bytes")?;
+        pragmas.push_str(&format!("PRAGMA mmap_size = {size};"));
+    }
+
+    if let Ok(store) = std::env::var("SQLITE_TEMP_STORE") {
+        if !["default", "file", "memory"].contains(&store.to_lowercase().as_str()) {
+            bail!("SQLITE_TEMP_STORE must be default, file or memory");
+        }
+        pragmas.push_str(&format!("PRAGMA temp_store = {store};"));
+    }
+
+    Ok(pragmas)
+}
+
+/// Reads a row of [`Db::stream_all_items`].
+fn exported_item(row: &rusqlite::Row) -> Result<ExportedItem> {
+    let url: String = row.get(0)?;
+    let vector: Option<Vec<u8>> = row.get(6)?;
+    let doc_vector = match vector {
+        Some(vector) => Some(
+            decode_vector(&vector, row.get(7)?)
+                .with_context(|| format!("bad doc vector for {url}"))?,
+        ),
+        None => None,
+    };
+
+    Ok(ExportedItem {
+        url,
+        title: row.get(1)?,
+        tags: split_tags(&row.get::<_, String>(2)?),
+        status: row.get(3)?,
+        time_added: row.get(4)?,
+        markdown: row.get(5)?,
+        doc_vector,
+    })
+}
+
+/// An item's decoded doc vector, with what we need to rank it.
+struct DocVector {
+    url: String,
+    title: String,
+    vector: Vec<f32>,
+    model: Option<String>,
+}
+
+/// Ranks `vectors` by cosine similarity to `target`, most similar first. Vectors from another
+/// model than `model` live in another space and can't be compared, so they're left out.
+fn rank_by_similarity(
+    vectors: &[DocVector],
+    target: &[f32],
+    model: Option<&str>,
+) -> Vec<SimilarItem> {
+    let mut ranked: Vec<_> = vectors
+        .iter()
+        .filter(|v| v.model.as_deref() == model && v.vector.len() == target.len())
+        .map(|v| SimilarItem {
+            url: v.url.clone(),
+            title: v.title.clone(),
+            similarity: cosine_similarity(target, &v.vector),
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    ranked
+}
+
+/// A doc vector as stored: its bytes, how many dimensions it has and the model it came from.
+type StoredVector = (Vec<u8>, Option<usize>, Option<String>);
+
+/// Encodes a vector the way we store it, as little-endian `f32`s.
+fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+/// Decodes a vector stored as little-endian `f32`s, checking it has `dims` dimensions if we
+/// know how many it should have. Fails on a blob that got cut off or mangled.
+fn decode_vector(bytes: &[u8], dims: Option<usize>) -> Result<Vec<f32>> {
+    if !bytes.len().is_multiple_of(4) {
+        bail!("{} bytes is not a whole number of f32s", bytes.len());
+    }
+    if let Some(dims) = dims.filter(|dims| *dims != bytes.len() / 4) {
+        bail!("{} dimensions instead of {dims}", bytes.len() / 4);
+    }
+
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
+}
+
+/// Brings an item's entry in the full-text index up to date.
+fn index_item(conn: &rusqlite::Connection, url: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM items_fts WHERE url = ?", params![url])?;
+    conn.execute(
+        "INSERT INTO items_fts (url, title, markdown)
+        SELECT url, title, markdown FROM items WHERE url = ? AND markdown IS NOT NULL",
+        params![url],
+    )?;
+
+    Ok(())
+}
+
+/// Saves a crawl for [`Db::save_crawl`] and [`Db::save_crawls`], which wrap it in a transaction.
+fn save_crawl(
+    conn: &rusqlite::Connection,
+    crawl: CrawledArticle,
+    policy: OverwritePolicy,
+) -> rusqlite::Result<bool> {
+    let url = crawl.url.to_string();
+
+    let existing: Option<(Option<u64>, Option<u16>, Option<String>)> = conn
+        .query_row(
+            "SELECT time_last_crawl, http_status_last_crawl, markdown
+            FROM items WHERE url = ?",
+            params![url],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+
+    // We only save crawls of items we know about.
+    let Some((time_crawl, status, markdown)) = existing else {
+        return Ok(false);
+    };
+
+    if let (OverwritePolicy::IfNotWorse, Some(markdown)) = (policy, &markdown) {
+        let was_error = status.is_some_and(|s| s >= 400);
+        let shorter = crawl.markdown.chars().count() < markdown.chars().count();
+        if shorter || (crawl.status.as_u16() >= 400 && !was_error) {
+            return Ok(false);
+        }
+    }
+
+    let latest: Option<String> = conn
+        .query_row(
+            "SELECT content_hash FROM versions WHERE url = ? ORDER BY id DESC LIMIT 1",
+            params![url],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    // Items crawled before we kept versions: their current markdown is the baseline.
+    let latest = match (latest, markdown) {
+        (Some(hash), _) => Some(hash),
+        (None, Some(markdown)) => {
+            let hash = content_hash(&markdown);
+            save_version(conn, &url, time_crawl, &hash, &markdown)?;
+            Some(hash)
+        }
+        (None, None) => None,
+    };
+
+    let hash = content_hash(&crawl.markdown);
+    if latest.as_ref() != Some(&hash) {
+        save_version(conn, &url, Some(crawl.timestamp), &hash, &crawl.markdown)?;
+    }
+
+    conn.execute(
+        "UPDATE items
+        SET time_last_crawl = ?, http_status_last_crawl = ?, raw_html = ?, html = ?,
+            markdown = ?, plain_text = ?, published_at = ?, author = ?, final_url = ?,
+            paywalled = ?
+        WHERE url = ?",
+        params![
+            crawl.timestamp,
+            crawl.status.as_u16(),
+            crawl.raw_html,
+            crawl.html,
+            crawl.markdown,
+            crawl.plain_text,
+            crawl.published,
+            crawl.author,
+            crawl.final_url.to_string(),
+            crawl.paywalled,
+            url
+        ],
+    )?;
+
+    conn.execute("DELETE FROM links WHERE item_url = ?", params![url])?;
+    for link in crawl.links {
+        conn.execute(
+            "INSERT INTO links (item_url, href, text) VALUES (?, ?, ?)",
+            params![url, link.href, link.text],
+        )?;
+    }
+
+    // Pocket sometimes has no title for an item, or just uses the URL.
+    if !crawl.title.is_empty() {
+        conn.execute(
+            "UPDATE items SET title = ? WHERE url = ? AND (trim(title) = '' OR title = url)",
+            params![crawl.title, url],
+        )?;
+    }
+
+    index_item(conn, &url)?;
+
+    Ok(true)
+}
+
+/// Length of an item's public id. 62^8 ids is plenty to never see a collision in one library.
+const PUB_ID_LEN: usize = 8;
+
+const BASE62: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Makes up a short random id to refer to an item by instead of its URL.
+fn new_pub_id() -> String {
+    let mut rng = rand::rng();
+
+    (0..PUB_ID_LEN)
+        .map(|_| char::from(BASE62[rng.random_range(0..BASE62.len())]))
+        .collect()
+}
+
+/// Gives every item without a public id one, e.g. items from before we had them.
+fn backfill_pub_ids(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    let rowids: Vec<i64> = conn
+        .prepare("SELECT rowid FROM items WHERE pub_id IS NULL")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    for rowid in rowids {
+        conn.execute(
+            "UPDATE items SET pub_id = ? WHERE rowid = ?",
+            params![new_pub_id(), rowid],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Rebuilds the full-text index from scratch.
+fn reindex_all(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "DELETE FROM items_fts;
+        INSERT INTO items_fts (url, title, markdown)
+        SELECT url, title, markdown FROM items WHERE markdown IS NOT NULL;",
+    )
+}
+
+/// Splits the comma-joined tags we store into separate tags.
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Crawls with less markdown than this probably didn't get the article.
+pub const SHORT_MARKDOWN_CHARS: usize = 500;
+
+/// How many versions of an item's markdown we keep around.
+pub const MAX_VERSIONS: usize = 5;
+
+fn content_hash(markdown: &str) -> String {
+    Sha256::digest(markdown)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Stores a version of an item's markdown and prunes the ones beyond [`MAX_VERSIONS`].
+fn save_version(
+    conn: &rusqlite::Connection,
+    url: &str,
+    time_crawl: Option<u64>,
+    hash: &str,
+    markdown: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO versions (url, time_crawl, content_hash, markdown) VALUES (?, ?, ?, ?)",
+        params![url, time_crawl, hash, markdown],
+    )?;
+
+    conn.execute(
+        "DELETE FROM versions WHERE url = ?1 AND id NOT IN (
+            SELECT id FROM versions WHERE url = ?1 ORDER BY id DESC LIMIT ?2
+        )",
+        params![url, MAX_VERSIONS],
+    )?;
+
+    Ok(())
+}
+
+/// Lists the column names of `schema.table`.
+fn table_columns(
+    conn: &rusqlite::Connection,
+    schema: &str,
+    table: &str,
+) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1, ?2)")?;
+    stmt.query_map(params![table, schema], |row| row.get(0))?
+        .collect()
+}
+
+/// Lists the columns `table` has in both the main and the attached `other` database.
+fn shared_columns(conn: &rusqlite::Connection, table: &str) -> rusqlite::Result<Vec<String>> {
+    let theirs = table_columns(conn, "other", table)?;
+
+    Ok(table_columns(conn, "main", table)?
+        .into_iter()
+        .filter(|c| theirs.contains(c))
+        .collect())
+}
+
+/// Does the work of [`Db::merge`] once `other` is attached, in a transaction that's rolled back
+/// if anything fails.
+fn merge_attached(conn: &mut rusqlite::Connection) -> rusqlite::Result<MergeReport> {
+    let tx = conn.transaction()?;
+
+    let total: usize = tx.query_row("SELECT COUNT(*) FROM other.items", [], |row| row.get(0))?;
+
+    // The other database may predate some of our migrations, so only copy the columns we both
+    // have. Public ids are only unique within a database, so new rows get fresh ones below.
+    let item_columns = shared_columns(&tx, "items")?;
+    let columns = item_columns
+        .iter()
+        .filter(|c| *c != "pub_id")
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    let added: Vec<String> = tx
+        .prepare(&format!(
+            "INSERT INTO main.items ({columns})
+            SELECT {columns} FROM other.items
+            WHERE url NOT IN (SELECT url FROM main.items)
+            RETURNING url"
+        ))?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let assignments = CONTENT_COLUMNS
+        .iter()
+        .filter(|c| item_columns.iter().any(|ic| ic == *c))
+        .map(|c| format!("{c} = o.{c}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let updated: Vec<String> = tx
+        .prepare(&format!(
+            "UPDATE main.items
+            SET {assignments}
+            FROM other.items AS o
+            WHERE o.url = items.url AND (
+                (o.doc_vector IS NOT NULL AND items.doc_vector IS NULL)
+                OR ((o.doc_vector IS NULL) = (items.doc_vector IS NULL) AND (
+                    length(coalesce(o.markdown, '')) > length(coalesce(items.markdown, ''))
+                    OR (length(coalesce(o.markdown, '')) = length(coalesce(items.markdown, ''))
+                        AND coalesce(o.time_last_crawl, 0) > coalesce(items.time_last_crawl, 0))
+                ))
+            )
+            RETURNING items.url"
+        ))?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    // Our chunks of the rows we updated were cut from content that is gone now.
+    for url in &updated {
+        tx.execute("DELETE FROM main.chunks WHERE url = ?", params![url])?;
+    }
+
+    let columns = shared_columns(&tx, "chunks")?
+        .into_iter()
+        .filter(|c| c != "id")
+        .collect::<Vec<_>>();
+    let select = columns
+        .iter()
+        .map(|c| format!("o.{c}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut chunks = 0;
+    {
+        let mut copy = tx.prepare(&format!(
+            "INSERT INTO main.chunks ({})
+            SELECT {select}
+            FROM other.chunks AS o
+            WHERE o.url = ?1 AND NOT EXISTS (
+                SELECT 1 FROM main.chunks AS c WHERE c.url = o.url AND c.chunk = o.chunk
+            )",
+            columns.join(", ")
+        ))?;
+        for url in added.iter().chain(&updated) {
+            chunks += copy.execute(params![url])?;
+        }
+    }
+
+    backfill_pub_ids(&tx)?;
+    // Easier than keeping track of which rows changed, and merges are rare.
+    reindex_all(&tx)?;
+
+    tx.commit()?;
+
+    Ok(MergeReport {
+        added: added.len(),
+        updated: updated.len(),
+        skipped: total - added.len() - updated.len(),
+        chunks,
+    })
+}
+
+/// Whether a crawl may replace content we already have for an item.
+#[derive(Debug, Clone, Copy)]
+pub enum OverwritePolicy {
+    /// Always store the new crawl.
+    Always,
+    /// Only store the new crawl if it isn't worse than what we have: it has at least as much
+    /// markdown, and it isn't an error page replacing a good page.
+    IfNotWorse,
+}
+
+/// Narrows down the items an export covers.
+#[derive(Debug, Default, Clone)]
+pub struct ExportFilter {
+    /// Only export items with this status, `unread` or `archive`.
+    pub status: Option<String>,
+    /// Only export items saved at or after this Unix timestamp.
+    pub since: Option<i64>,
+    /// Only export items whose URL matches.
+    pub url_pattern: Option<Regex>,
+}
+
+impl ExportFilter {
+    /// Adds the filter's conditions to `sql`, which must end in a `WHERE` clause.
+    fn apply(&self, sql: &str) -> (String, Vec<Value>) {
+        let mut sql = sql.to_string();
+        let mut values = vec![];
+
+        if let Some(status) = &self.status {
+            sql.push_str(" AND status = ?");
+            values.push(Value::Text(status.clone()));
+        }
+
+        if let Some(since) = self.since {
+            sql.push_str(" AND time_added >= ?");
+            values.push(Value::Integer(since));
+        }
+
+        if let Some(re) = &self.url_pattern {
+            sql.push_str(" AND url REGEXP ?");
+            values.push(Value::Text(re.as_str().to_string()));
+        }
+
+        (sql, values)
+    }
+}
+
+/// Narrows down the items a candidate query (crawl, embed, ...) returns.
+#[derive(Debug, Default, Clone)]
+pub struct CandidateFilter {
+    /// Return at most this many items.
+    pub limit: Option<usize>,
+    /// Only return items whose URL matches.
+    pub url_pattern: Option<Regex>,
+    /// Only return items with this tag.
+    pub tag: Option<String>,
+    /// Only return items with this status, `unread` or `archive`.
+    pub status: Option<String>,
+    /// Only return items saved at or after this Unix timestamp.
+    pub since: Option<i64>,
+    /// Pick items at random rather than in insertion order.
+    pub random: bool,
+}
+
+impl CandidateFilter {
+    /// Adds the filter's conditions and limit to `sql`, which must end in a `WHERE` clause.
+    fn apply(&self, sql: &str) -> (String, Vec<Value>) {
+        let mut sql = sql.to_string();
+        let mut values = vec![];
+
+        if let Some(re) = &self.url_pattern {
+            sql.push_str(" AND url REGEXP ?");
+            values.push(Value::Text(re.as_str().to_string()));
+        }
+
+        if let Some(tag) = &self.tag {
+            // Tags are stored joined by commas, so wrapping both in commas only matches whole tags.
+            sql.push_str(" AND instr(',' || coalesce(tags, '') || ',', ',' || ? || ',') > 0");
+            values.push(Value::Text(tag.clone()));
+        }
+
+        if let Some(status) = &self.status {
+            sql.push_str(" AND status = ?");
+            values.push(Value::Text(status.clone()));
+        }
+
+        if let Some(since) = self.since {
+            sql.push_str(" AND time_added >= ?");
+            values.push(Value::Integer(since));
+        }
+
+        if self.random {
+            sql.push_str(" ORDER BY RANDOM()");
+        }
+
+        if let Some(n) = self.limit {
+            sql.push_str(&format!(" LIMIT {n}"));
+        }
+
+        (sql, values)
+    }
+}
+
+/// Narrows down the items the crawl status histogram and the library stats count.
+#[derive(Debug, Default, Clone)]
+pub struct HistogramFilter {
+    /// Only count items last crawled at or after this Unix timestamp.
+    pub since: Option<i64>,
+    /// Only count items last crawled before this Unix timestamp.
+    pub until: Option<i64>,
+    /// Only count items with this status, `unread` or `archive`.
+    pub status: Option<String>,
+}
+
+impl HistogramFilter {
+    /// Adds the filter's conditions to `sql`, which must end in a `WHERE` clause.
+    fn apply(&self, sql: &str) -> (String, Vec<Value>) {
+        let mut sql = sql.to_string();
+        let mut values = vec![];
+
+        if let Some(since) = self.since {
+            sql.push_str(" AND time_last_crawl >= ?");
+            values.push(Value::Integer(since));
+        }
+
+        if let Some(until) = self.until {
+            sql.push_str(" AND time_last_crawl < ?");
+            values.push(Value::Integer(until));
+        }
+
+        if let Some(status) = &self.status {
+            sql.push_str(" AND status = ?");
+            values.push(Value::Text(status.clone()));
+        }
+
+        (sql, values)
+    }
+}
+
+#[derive(Debug)]
+pub struct ItemHandle {
+    pub url: Url,
+}
+
+#[derive(Debug)]
+pub struct ItemForChunking {
+    pub url: Url,
+    /// Markdown rather than plain text, so the chunker can go by its headings.
+    pub markdown: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UrlWithDocVector {
+    pub url: String,
+    pub vector: Vec<f32>,
+    /// The model the vector was embedded with.
+    pub model: Option<String>,
+}
+
+/// Aggregate numbers about the library, as [`Db::get_library_stats`] returns them.
+#[derive(Debug, Serialize)]
+pub struct LibraryStats {
+    pub total: usize,
+    pub by_status: BTreeMap<String, usize>,
+    pub with_markdown: usize,
+    pub embedded: usize,
+    pub total_words: usize,
+    /// Over the items we have text for.
+    pub avg_reading_minutes: Option<f64>,
+    /// Items we've tried crawling, whether it worked or not.
+    pub crawled: usize,
+    /// Share of crawled items that got a 2xx response.
+    pub crawl_success_rate: Option<f64>,
+    /// Crawls that got a paywall or login wall instead of the article.
+    pub paywalled: usize,
+}
+
+/// A failed crawl, as [`Db::get_crawl_errors`] returns it.
+#[derive(Debug)]
+pub struct CrawlErrorRecord {
+    pub url: String,
+    pub at: i64,
+    pub message: String,
+}
+
+/// An item with its content, as [`Db::stream_all_items`] returns it.
+#[derive(Debug, Serialize)]
+pub struct ExportedItem {
+    pub url: String,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub status: String,
+    pub time_added: i64,
+    pub markdown: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc_vector: Option<Vec<f32>>,
+}
+
+/// An item's metadata, as [`Db::get_item_meta`] returns it.
+#[derive(Debug)]
+pub struct ItemMeta {
+    pub url: String,
+    pub title: String,
+    pub status: String,
+    pub time_added: i64,
+    pub time_last_crawl: Option<i64>,
+    pub http_status: Option<u16>,
+    pub final_url: Option<String>,
+    pub author: Option<String>,
+    pub published_at: Option<i64>,
+    pub paywalled: bool,
+    /// Short id to refer to the item by, which unlike its URL never changes.
+    pub pub_id: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct SimilarItem {
+    pub url: String,
+    pub title: String,
+    /// Cosine similarity of the doc vectors, from -1 to 1.
+    pub similarity: f32,
+}
+
+#[derive(Debug)]
+pub struct SearchHit {
+    pub url: String,
+    pub title: String,
+    /// Bit of the markdown around the match, with matched terms in `**`.
+    pub snippet: String,
+}
+
+/// Outcome of [`Db::merge`].
+#[derive(Debug)]
+pub struct MergeReport {
+    pub added: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub chunks: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::pocket::PocketReader;
+
+    fn pocket_item(status: &str) -> PocketItem {
+        let csv = format!(
+            "title,url,time_added,tags,status\nA title,https://example.com/a,1700000000,,{status}\n"
+        );
+
+        PocketReader::new(csv.as_bytes())
+            .read()
+            .unwrap()
+            .items
+            .remove(0)
+            .1
+    }
+
+    async fn status(db: &Db) -> String {
+        db.conn
+            .call(|conn| {
+                conn.query_row("SELECT status FROM items", [], |row| {
+                    row.get::<_, String>(0)
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn reimport_keeps_local_status() {
+        let db = Db::new(PathBuf::from(":memory:")).await.unwrap();
+        let url = Url::parse("https://example.com/a").unwrap();
+
+        db.save_item(pocket_item("unread")).await.unwrap();
+        let changed = db.set_status(url.clone(), PocketStatus::Archive).await;
+        assert_eq!(changed.unwrap(), Some(true));
+        let changed = db.set_status(url.clone(), PocketStatus::Archive).await;
+        assert_eq!(changed.unwrap(), Some(false));
+
+        // An export from before we archived it here.
+        assert!(!db.save_item(pocket_item("unread")).await.unwrap());
+        assert_eq!(status(&db).await, "archive");
+
+        db.set_status(url, PocketStatus::Unread).await.unwrap();
+        assert!(!db.save_item(pocket_item("archive")).await.unwrap());
+        assert_eq!(status(&db).await, "unread");
+    }
+
+    #[tokio::test]
+    async fn items_by_pub_id() {
+        let db = Db::new(PathBuf::from(":memory:")).await.unwrap();
+        db.save_item(pocket_item("unread")).await.unwrap();
+        let url = "https://example.com/a".to_string();
+
+        let meta = db.get_item_meta(url.clone()).await.unwrap().unwrap();
+        assert_eq!(meta.pub_id.len(), PUB_ID_LEN);
+        assert!(meta.pub_id.chars().all(|c| c.is_ascii_alphanumeric()));
+
+        let by_id = db
+            .get_item_meta(meta.pub_id.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(by_id.url, url);
+
+        // Re-importing the item keeps its id.
+        db.save_item(pocket_item("archive")).await.unwrap();
+        let again = db.get_item_meta(url).await.unwrap().unwrap();
+        assert_eq!(again.pub_id, meta.pub_id);
+    }
+
+    #[tokio::test]
+    async fn candidates_by_tag_status_and_age() {
+        let db = Db::new(PathBuf::from(":memory:")).await.unwrap();
+        db.conn
+            .call(|conn| {
+                conn.execute_batch(
+                    "INSERT INTO items (url, title, time_added, tags, status) VALUES
+                        ('https://example.com/a', 'A', 1, 'rust', 'unread'),
+                        ('https://example.com/b', 'B', 2, 'go,rust', 'archive'),
+                        ('https://example.com/c', 'C', 3, 'rustacean,trust', 'unread'),
+                        ('https://example.com/d', 'D', 4, '', 'unread');",
+                )
+            })
+            .await
+            .unwrap();
+
+        let urls = |filter| async {
+            db.get_uncrawled_items(filter)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|item| item.url.path().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let rust = CandidateFilter {
+            tag: Some("rust".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(urls(rust.clone()).await, ["/a", "/b"]);
+
+        let unread_rust = CandidateFilter {
+            status: Some("unread".to_string()),
+            ..rust
+        };
+        assert_eq!(urls(unread_rust).await, ["/a"]);
+
+        let recent = CandidateFilter {
+            since: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(urls(recent).await, ["/c", "/d"]);
+    }
+
+    #[test]
+    fn corrupt_vectors() {
+        let bytes = encode_vector(&[1.0, -2.5]);
+        assert_eq!(decode_vector(&bytes, Some(2)).unwrap(), [1.0, -2.5]);
+        assert_eq!(decode_vector(&bytes, None).unwrap(), [1.0, -2.5]);
+
+        assert!(decode_vector(&bytes[..7], None).is_err());
+        assert!(decode_vector(&bytes, Some(3)).is_err());
+    }
+
+    #[tokio::test]
+    async fn similarity_within_one_model() {
+        let db = Db::new(PathBuf::from(":memory:")).await.unwrap();
+        let url = |path: &str| Url::parse(&format!("https://example.com/{path}")).unwrap();
+        for (path, vector, model) in [
+            ("a", [1.0, 0.0], "small"),
+            ("b", [1.0, 0.1], "small"),
+            ("c", [1.0, 0.0], "large"),
+        ] {
+            db.save_url(url(path), String::new()).await.unwrap();
+            db.save_doc_vector(url(path), &vector, model.to_string())
+                .await
+                .unwrap();
+        }
+
+        let similar = db.get_similar(url("a"), 10, -1.0).await.unwrap();
+        assert_eq!(similar.len(), 1);
+        assert_eq!(similar[0].url, "https://example.com/b");
+
+        let nearest = db
+            .get_nearest(vec![0.0, 1.0], "small".to_string(), 10)
+            .await
+            .unwrap();
+        let urls: Vec<_> = nearest.iter().map(|item| item.url.as_str()).collect();
+        assert_eq!(urls, ["https://example.com/b", "https://example.com/a"]);
+
+        db.conn
+            .call(|conn| conn.execute("UPDATE items SET markdown = 'm'", []))
+            .await
+            .unwrap();
+        let large = db
+            .get_urls_with_doc_vector(Some("large".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(large.len(), 1);
+        assert_eq!(large[0].url, "https://example.com/c");
+    }
+
+    #[tokio::test]
+    async fn embeddings_replace_earlier_ones() {
+        let db = Db::new(PathBuf::from(":memory:")).await.unwrap();
+        db.save_item(pocket_item("unread")).await.unwrap();
+        let url = Url::parse("https://example.com/a").unwrap();
+
+        let chunk = |text: &str, vector: Vec<f32>| (text.to_string(), vector);
+        db.save_embeddings(
+            url.clone(),
+            vec![chunk("one", vec![1.0, 0.0]), chunk("two", vec![0.0, 1.0])],
+            &[0.5, 0.5],
+            "small".to_string(),
+        )
+        .await
+        .unwrap();
+        db.save_embeddings(
+            url,
+            vec![chunk("three", vec![1.0, 1.0])],
+            &[1.0, 1.0],
+            "small".to_string(),
+        )
+        .await
+        .unwrap();
+
+        let chunks: Vec<String> = db
+            .conn
+            .call(|conn| {
+                conn.prepare("SELECT chunk FROM chunks")?
+                    .query_map([], |row| row.get(0))?
+                    .collect()
+            })
+            .await
+            .unwrap();
+        assert_eq!(chunks, ["three"]);
+
+        let mut items = db.stream_all_items(ExportFilter::default());
+        let item = items.recv().await.unwrap().unwrap();
+        assert_eq!(item.doc_vector, Some(vec![1.0, 1.0]));
+    }
+
+    #[tokio::test]
+    async fn streams_items() {
+        let db = Db::new(PathBuf::from(":memory:")).await.unwrap();
+        db.save_item(pocket_item("archive")).await.unwrap();
+        let url = Url::parse("https://example.com/a").unwrap();
+        db.save_doc_vector(url, &[0.5, 1.0], "small".to_string())
+            .await
+            .unwrap();
+
+        let archived = ExportFilter {
+            status: Some("archive".to_string()),
+            ..ExportFilter::default()
+        };
+        let mut items = db.stream_all_items(archived);
+        let item = items.recv().await.unwrap().unwrap();
+        assert_eq!(item.title, "A title");
+        assert_eq!(item.doc_vector, Some(vec![0.5, 1.0]));
+        assert!(items.recv().await.is_none());
+
+        let unread = ExportFilter {
+            status: Some("unread".to_string()),
+            ..ExportFilter::default()
+        };
+        assert!(db.stream_all_items(unread).recv().await.is_none());
+
+        // The item was saved in 2023.
+        let recent = ExportFilter {
+            since: Some(1800000000),
+            ..ExportFilter::default()
+        };
+        assert!(db.stream_all_items(recent).recv().await.is_none());
+
+        let elsewhere = ExportFilter {
+            url_pattern: Some(Regex::new(r"substack\.com").unwrap()),
+            ..ExportFilter::default()
+        };
+        assert!(db.stream_all_items(elsewhere).recv().await.is_none());
+        let here = ExportFilter {
+            url_pattern: Some(Regex::new(r"example\.com/a$").unwrap()),
+            ..ExportFilter::default()
+        };
+        assert!(db.stream_all_items(here).recv().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn merges_rows_with_their_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let ours = Db::new(dir.join("ours.db")).await.unwrap();
+        let theirs = Db::new(dir.join("theirs.db")).await.unwrap();
+
+        async fn add(db: &Db, url: &'static str) {
+            db.conn
+                .call(move |conn| {
+                    conn.execute(
+                        "INSERT INTO items (url, title, time_added, tags, status)
+                        VALUES (?, '', 1, '', 'unread')",
+                        params![url],
+                    )
+                })
+                .await
+                .unwrap();
+        }
+        async fn embed(db: &Db, url: &str, chunk: &str) {
+            let chunks = vec![(chunk.to_string(), vec![1.0])];
+            db.save_embeddings(
+                Url::parse(url).unwrap(),
+                chunks,
+                &[1.0],
+                "small".to_string(),
+            )
+            .await
+            .unwrap();
+        }
+
+        // Theirs is richer for a, ours is for b, and only they have c.
+        add(&ours, "https://example.com/a").await;
+        add(&ours, "https://example.com/b").await;
+        embed(&ours, "https://example.com/b", "our b").await;
+        add(&theirs, "https://example.com/a").await;
+        embed(&theirs, "https://example.com/a", "their a").await;
+        add(&theirs, "https://example.com/b").await;
+        add(&theirs, "https://example.com/c").await;
+        embed(&theirs, "https://example.com/c", "their c").await;
+        ours.conn
+            .call(|conn| {
+                conn.execute(
+                    "INSERT INTO chunks (url, chunk, vector) VALUES ('https://example.com/a', 'our a', x'')",
+                    [],
+                )
+            })
+            .await
+            .unwrap();
+        theirs
+            .conn
+            .call(|conn| {
+                conn.execute(
+                    "INSERT INTO chunks (url, chunk, vector) VALUES ('https://example.com/b', 'their b', x'')",
+                    [],
+                )
+            })
+            .await
+            .unwrap();
+        drop(theirs);
+
+        // A failed merge mustn't leave anything attached.
+        fs::write(dir.join("broken.db"), "not a database").unwrap();
+        assert!(ours.merge(dir.join("broken.db")).await.is_err());
+
+        let report = ours.merge(dir.join("theirs.db")).await.unwrap();
+        assert_eq!(
+            (report.added, report.updated, report.skipped, report.chunks),
+            (1, 1, 1, 2)
+        );
+
+        let mut chunks: Vec<String> = ours
+            .conn
+            .call(|conn| {
+                conn.prepare("SELECT chunk FROM chunks")?
+                    .query_map([], |row| row.get(0))?
+                    .collect()
+            })
+            .await
+            .unwrap();
+        chunks.sort();
+        assert_eq!(chunks, ["our b", "their a", "their c"]);
+    }
 }
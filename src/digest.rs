@@ -0,0 +1,59 @@
+//! Picks a reading session's worth of unread articles.
+
+use clap::ValueEnum;
+
+/// Reading speed we assume, same as `scripts/reading_time.py`.
+pub const WORDS_PER_MINUTE: usize = 200;
+
+/// Estimates how long `text` takes to read, in minutes.
+pub fn reading_minutes(text: &str) -> f64 {
+    text.split_whitespace().count() as f64 / WORDS_PER_MINUTE as f64
+}
+
+/// Order in which unread articles are considered for a digest.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DigestStrategy {
+    /// longest-waiting articles first
+    Oldest,
+    /// a random pick
+    Random,
+    /// quick reads first
+    Shortest,
+    /// earliest published first, or earliest saved if we don't know when it was published
+    Published,
+}
+
+impl DigestStrategy {
+    /// The `ORDER BY` clause for this strategy over `items`.
+    pub fn order_by(&self) -> &'static str {
+        match self {
+            DigestStrategy::Oldest => "time_added ASC",
+            DigestStrategy::Random => "RANDOM()",
+            DigestStrategy::Shortest => "markdown IS NULL, length(markdown) ASC",
+            DigestStrategy::Published => "coalesce(published_at, time_added) ASC",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DigestItem {
+    pub url: String,
+    pub title: String,
+    pub minutes: f64,
+}
+
+/// Greedily takes `candidates`, in order, as long as they fit in a budget of `minutes`.
+pub fn select(candidates: Vec<DigestItem>, minutes: u32) -> Vec<DigestItem> {
+    let mut budget = f64::from(minutes);
+
+    candidates
+        .into_iter()
+        .filter(|item| {
+            let fits = item.minutes <= budget;
+            if fits {
+                budget -= item.minutes;
+            }
+            fits
+        })
+        .collect()
+}
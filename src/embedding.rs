@@ -0,0 +1,235 @@
+//! Embeddings through the OpenRouter API, or a local model with the `local-embeddings` feature.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use indicatif::ProgressBar;
+use reqwest::{
+    Client, StatusCode,
+    header::{HeaderMap, RETRY_AFTER},
+};
+use serde::{Deserialize, Serialize};
+use tokio::time;
+
+const ENDPOINT: &str = "https://openrouter.ai/api/v1/embeddings";
+/// The model we embed with unless asked for another, and the one everything embedded before we
+/// kept track of models was embedded with.
+pub const DEFAULT_MODEL: &str = "qwen/qwen3-embedding-8b";
+
+/// Something that turns text into embedding vectors, cheap to clone into concurrent tasks.
+pub trait Embedder: Clone + Send + Sync + 'static {
+    /// Embeds the chunks of one article, returning a vector for each chunk in the same order.
+    fn embed(&self, chunks: &[&str]) -> impl Future<Output = Result<Vec<Vec<f32>>>> + Send;
+
+    /// The name of the model we embed with, which we store along with the vectors.
+    fn model(&self) -> &str;
+}
+
+/// Client for an embedding model on OpenRouter.
+#[derive(Clone)]
+pub struct OpenRouterEmbedder {
+    client: Client,
+    api_key: String,
+    model: String,
+    /// The progress bar of the run we embed for, so our notices don't garble it.
+    progress: ProgressBar,
+}
+
+impl OpenRouterEmbedder {
+    pub const ENV_VAR: &str = "OPENROUTER_API_KEY";
+
+    /// Creates an [`OpenRouterEmbedder`] for `model` with the API key from the environment.
+    pub fn from_env(client: Client, model: String) -> Result<Self> {
+        let api_key = std::env::var(Self::ENV_VAR)
+            .with_context(|| format!("{} is not set", Self::ENV_VAR))?;
+
+        Ok(Self {
+            client,
+            api_key,
+            model,
+            progress: ProgressBar::hidden(),
+        })
+    }
+
+    /// Prints notices, like being rate limited, above `progress` rather than through it.
+    pub fn with_progress(mut self, progress: ProgressBar) -> Self {
+        self.progress = progress;
+        self
+    }
+}
+
+impl Embedder for OpenRouterEmbedder {
+    /// When rate limited, waits as long as the API asks, or backs off if it doesn't say.
+    async fn embed(&self, chunks: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let req = EmbeddingRequest {
+            model: self.model.clone(),
+            input: chunks.to_vec(),
+        };
+
+        let mut attempt = 0;
+        let res = loop {
+            let res = self
+                .client
+                .post(ENDPOINT)
+                .header("Authorization", format!("Bearer {}", &self.api_key))
+                .header("Content-Type", "application/json")
+                .json(&req)
+                .send()
+                .await?;
+
+            if res.status() != StatusCode::TOO_MANY_REQUESTS || attempt == RATE_LIMIT_RETRIES {
+                break res;
+            }
+
+            let wait = retry_after(res.headers()).unwrap_or(Duration::from_secs(2 << attempt));
+            attempt += 1;
+            self.progress
+                .suspend(|| eprintln!("rate limited, retrying in {}s", wait.as_secs_f32()));
+            time::sleep(wait).await;
+        };
+
+        let status = res.status();
+        let body = res.text().await.context("failed to read response")?;
+        let embedding = parse_embedding_response(status, &body)?;
+
+        let mut data = embedding.data;
+        if data.len() != chunks.len() {
+            return Err(anyhow!(
+                "got {} embeddings for {} chunks",
+                data.len(),
+                chunks.len()
+            ));
+        }
+        data.sort_by_key(|d| d.index);
+
+        Ok(data.into_iter().map(|d| d.embedding).collect())
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+/// How often we try again after being rate limited before giving up on an article.
+const RATE_LIMIT_RETRIES: u32 = 5;
+
+/// Reads a `Retry-After` header in seconds. The HTTP date form isn't worth supporting, as
+/// OpenRouter doesn't send it.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let secs = headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some(Duration::from_secs(secs))
+}
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest<'a> {
+    model: String,
+    input: Vec<&'a str>,
+}
+
+// Mirrors the full OpenRouter response, even the parts we don't use (yet).
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    id: String,
+    object: String,
+    data: Vec<EmbeddingData>,
+    model: String,
+    usage: Usage,
+    provider: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Deserialize)]
+struct EmbeddingData {
+    object: String,
+    embedding: Vec<f32>,
+    index: usize,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct Usage {
+    prompt_tokens: usize,
+    total_tokens: usize,
+    cost: f32,
+}
+
+/// What OpenRouter sends back instead of embeddings when something is wrong on their end.
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: ApiError,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+/// How much of an unparseable response body we show.
+const SNIPPET_LEN: usize = 500;
+
+/// Parses an embedding response body, surfacing the API's own error message if it sent one and a
+/// snippet of the raw body if it sent something we don't understand at all.
+fn parse_embedding_response(status: StatusCode, body: &str) -> Result<EmbeddingResponse> {
+    let err = match serde_json::from_str::<EmbeddingResponse>(body) {
+        Ok(embedding) => return Ok(embedding),
+        Err(err) => err,
+    };
+
+    if let Ok(ErrorResponse { error }) = serde_json::from_str(body) {
+        return Err(anyhow!("OpenRouter error ({status}): {}", error.message));
+    }
+
+    let snippet: String = body.chars().take(SNIPPET_LEN).collect();
+    let ellipsis = if snippet.len() < body.len() {
+        "..."
+    } else {
+        ""
+    };
+
+    Err(anyhow!(
+        "unexpected response ({status}): {err}\n{snippet}{ellipsis}"
+    ))
+}
+
+/// Cosine similarity of two vectors, or 0 if either is all zeros or they differ in length.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms =
+        a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn retry_after_seconds() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+}
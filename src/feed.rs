@@ -0,0 +1,129 @@
+//! Atom feed of the most recently saved items, to follow the reading list from a feed reader.
+
+use std::fmt::Write;
+
+use crate::opml::escape;
+
+/// An item on the reading list, as it shows up in the feed.
+#[derive(Debug)]
+pub struct FeedItem {
+    pub url: String,
+    pub title: String,
+    /// When the item was saved, as a Unix timestamp.
+    pub time_added: i64,
+    /// The start of the article's markdown, if we crawled it.
+    pub markdown: Option<String>,
+}
+
+/// How many items go in a feed, newest first. Feed readers only look at the latest ones anyway.
+pub const FEED_LEN: usize = 50;
+
+/// How many characters of an article make up its summary.
+pub const SUMMARY_LEN: usize = 300;
+
+/// Renders `items` as an Atom 1.0 feed with one entry per item. The feed is as new as its newest
+/// item, so it doesn't look updated to readers when nothing was saved.
+pub fn to_atom(title: &str, items: &[FeedItem]) -> String {
+    let mut atom = String::new();
+    let updated = items.iter().map(|item| item.time_added).max().unwrap_or(0);
+
+    atom.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    atom.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    let _ = writeln!(atom, "  <title>{}</title>", escape(title));
+    let _ = writeln!(
+        atom,
+        "  <id>urn:{}:{}</id>",
+        env!("CARGO_PKG_NAME"),
+        slug(title)
+    );
+    let _ = writeln!(atom, "  <updated>{}</updated>", rfc3339(updated));
+    let _ = writeln!(atom, "  <generator>{}</generator>", env!("CARGO_PKG_NAME"));
+
+    for item in items {
+        // Atom requires a title, and Pocket doesn't always have one.
+        let title = if item.title.trim().is_empty() {
+            &item.url
+        } else {
+            &item.title
+        };
+        atom.push_str("  <entry>\n");
+        let _ = writeln!(atom, "    <title>{}</title>", escape(title));
+        let _ = writeln!(atom, "    <link href=\"{}\"/>", escape(&item.url));
+        let _ = writeln!(atom, "    <id>{}</id>", escape(&item.url));
+        let _ = writeln!(atom, "    <updated>{}</updated>", rfc3339(item.time_added));
+        if let Some(summary) = item.markdown.as_deref().and_then(summary) {
+            let _ = writeln!(atom, "    <summary>{}</summary>", escape(&summary));
+        }
+        atom.push_str("  </entry>\n");
+    }
+
+    atom.push_str("</feed>\n");
+    atom
+}
+
+/// The first [`SUMMARY_LEN`] characters of `markdown`, cut at a word and on one line.
+fn summary(markdown: &str) -> Option<String> {
+    let text = markdown.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() <= SUMMARY_LEN {
+        return Some(text);
+    }
+
+    let cut: String = text.chars().take(SUMMARY_LEN).collect();
+    let cut = cut
+        .rsplit_once(' ')
+        .map_or(cut.as_str(), |(words, _)| words);
+    Some(format!("{cut}…"))
+}
+
+fn slug(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn rfc3339(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|dt| dt.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries() {
+        let items = [
+            FeedItem {
+                url: "https://example.com/?a=1&b=2".to_string(),
+                title: "Tom & Jerry".to_string(),
+                time_added: 1614076299,
+                markdown: Some(format!("# Cats\n\n{}", "meow ".repeat(100))),
+            },
+            FeedItem {
+                url: "https://example.com/untitled".to_string(),
+                title: String::new(),
+                time_added: 1614076300,
+                markdown: None,
+            },
+        ];
+
+        let atom = to_atom("Reading list (unread)", &items);
+
+        assert!(atom.contains("<id>urn:reading-addiction:readinglistunread</id>"));
+        assert!(atom.contains("<updated>2021-02-23T10:31:40Z</updated>"));
+        assert!(atom.contains("<link href=\"https://example.com/?a=1&amp;b=2\"/>"));
+        assert!(atom.contains("<title>https://example.com/untitled</title>"));
+        assert_eq!(atom.matches("<summary>").count(), 1);
+
+        let summary = summary(items[0].markdown.as_ref().unwrap()).unwrap();
+        assert!(summary.starts_with("# Cats meow meow"));
+        assert!(summary.ends_with("meow…"));
+        assert!(summary.chars().count() <= SUMMARY_LEN + 1);
+    }
+}
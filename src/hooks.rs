@@ -0,0 +1,40 @@
+//! Hooks that tell other services about changes to the reading list.
+
+use anyhow::{Context, Result};
+use reqwest::{Client, Url};
+use serde::Serialize;
+
+/// Posts items that got archived to a webhook, e.g. to push them into a permanent archive like
+/// Linkwarden. Configured through the `ON_ARCHIVE_URL` environment variable.
+pub struct ArchiveHook {
+    client: Client,
+    endpoint: Url,
+}
+
+impl ArchiveHook {
+    pub const ENV_VAR: &str = "ON_ARCHIVE_URL";
+
+    /// Creates an [`ArchiveHook`] if one is configured.
+    pub fn from_env(client: Client) -> Result<Option<Self>> {
+        let Ok(endpoint) = std::env::var(Self::ENV_VAR) else {
+            return Ok(None);
+        };
+
+        let endpoint = Url::parse(&endpoint)
+            .with_context(|| format!("{} is not a valid URL", Self::ENV_VAR))?;
+
+        Ok(Some(Self { client, endpoint }))
+    }
+
+    /// Posts `item` as JSON to the configured endpoint.
+    pub async fn notify(&self, item: &impl Serialize) -> Result<()> {
+        self.client
+            .post(self.endpoint.clone())
+            .json(item)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+//! Readers for the exports of other read-it-later services, turning them into the same
+//! [`PocketItem`]s a Pocket export gives us.
+
+use std::{
+    fs,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use anyhow::{Error, anyhow};
+use chrono::DateTime;
+use clap::ValueEnum;
+use reqwest::Url;
+use serde::Deserialize;
+use zip::ZipArchive;
+
+use crate::pocket::{Export, PocketItem, PocketReader, PocketStatus, Tag};
+
+/// Services we can import a reading list from.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ImportSource {
+    /// a Pocket export, as CSV, ZIP or JSON
+    Pocket,
+    /// an Instapaper CSV export
+    Instapaper,
+    /// an Omnivore export, as the ZIP it hands out or one of the JSON files in it
+    Omnivore,
+}
+
+impl ImportSource {
+    /// Reads the export at `path`.
+    pub fn read(self, path: impl AsRef<Path>) -> Result<Export, Error> {
+        match self {
+            ImportSource::Pocket => PocketReader::from_path(path),
+            ImportSource::Instapaper => InstapaperReader::new(fs::File::open(path)?).read(),
+            ImportSource::Omnivore => OmnivoreReader::from_path(path),
+        }
+    }
+}
+
+/// Reader for Instapaper CSV export files.
+pub struct InstapaperReader<R> {
+    reader: R,
+}
+
+impl<R> InstapaperReader<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    /// Processes all CSV rows into [`PocketItem`]s. Instapaper keeps read articles in its
+    /// "Archive" folder, so that becomes the status. Any other folder than "Unread" becomes a tag.
+    pub fn read(self) -> Result<Export, Error> {
+        let mut reader = csv::Reader::from_reader(self.reader);
+        let mut export = Export::default();
+
+        for (i, row) in reader.deserialize().enumerate() {
+            let item = row.map_err(Error::from).map(|row: InstapaperRow| {
+                let (status, tags) = match row.folder.as_str() {
+                    "Archive" => (PocketStatus::Archive, vec![]),
+                    "Unread" | "" => (PocketStatus::Unread, vec![]),
+                    folder => (PocketStatus::Unread, vec![Tag(folder.to_string())]),
+                };
+
+                PocketItem {
+                    title: row.title,
+                    url: row.url,
+                    time_added: row.timestamp,
+                    tags: tags.into_iter().collect(),
+                    status,
+                }
+            });
+            export.push(i + 1, item);
+        }
+
+        Ok(export)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct InstapaperRow {
+    #[serde(rename = "URL")]
+    url: Url,
+    #[serde(rename = "Title")]
+    title: String,
+    #[serde(rename = "Folder")]
+    folder: String,
+    #[serde(rename = "Timestamp")]
+    timestamp: usize,
+}
+
+/// Reader for Omnivore exports: a ZIP with the items' metadata spread over JSON files.
+pub struct OmnivoreReader<R> {
+    reader: R,
+}
+
+impl<R> OmnivoreReader<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    /// Processes one metadata JSON file into [`PocketItem`]s. Labels become tags.
+    pub fn read(self) -> Result<Export, Error> {
+        let values: Vec<serde_json::Value> = serde_json::from_reader(self.reader)?;
+        let mut export = Export::default();
+
+        for (i, value) in values.into_iter().enumerate() {
+            export.push(i + 1, omnivore_item(value));
+        }
+
+        Ok(export)
+    }
+}
+
+impl OmnivoreReader<fs::File> {
+    /// Reads all the metadata files in an export ZIP, or a single metadata file.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Export, Error> {
+        let bytes = fs::read(path)?;
+        if !bytes.starts_with(b"PK\x03\x04") {
+            return OmnivoreReader::new(bytes.as_slice()).read();
+        }
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let mut export = Export::default();
+        let mut found_metadata = false;
+
+        for i in 0..archive.len() {
+            let file = archive.by_index(i)?;
+            let name = file.name()?.into_owned();
+            // Articles' content sits next to the metadata, which we crawl ourselves anyway.
+            let is_metadata = name
+                .rsplit('/')
+                .next()
+                .is_some_and(|name| name.starts_with("metadata") && name.ends_with(".json"));
+            if !is_metadata {
+                continue;
+            }
+
+            found_metadata = true;
+            export.extend(OmnivoreReader::new(file).read()?, &name);
+        }
+
+        if !found_metadata {
+            return Err(anyhow!("no metadata files in the Omnivore export"));
+        }
+
+        Ok(export)
+    }
+}
+
+/// Turns one item of an Omnivore metadata file into a [`PocketItem`].
+fn omnivore_item(value: serde_json::Value) -> Result<PocketItem, Error> {
+    let item: OmnivoreItem = serde_json::from_value(value)?;
+    let time_added = DateTime::parse_from_rfc3339(&item.saved_at)?.timestamp();
+    let status = match item.state.as_str() {
+        "Archived" => PocketStatus::Archive,
+        _ => PocketStatus::Unread,
+    };
+
+    Ok(PocketItem {
+        title: item.title,
+        url: item.url,
+        time_added: usize::try_from(time_added)?,
+        tags: item.labels.into_iter().map(Tag).collect(),
+        status,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OmnivoreItem {
+    title: String,
+    url: Url,
+    state: String,
+    saved_at: String,
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+
+    #[test]
+    fn instapaper() {
+        let data = "\
+URL,Title,Selection,Folder,Timestamp
+https://en.wikipedia.org/wiki/Taoism,Taoism,,Unread,1614076299
+https://en.wikipedia.org/wiki/Zen,Zen,the sound of one hand,Archive,1614076300
+https://www.yudkowsky.net/rational/virtues,Twelve Virtues,,Rationality,1642196007";
+
+        let export = InstapaperReader::new(data.as_bytes()).read().unwrap();
+        let items: Vec<_> = export.items.into_iter().map(|(_, item)| item).collect();
+
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items[0].status, PocketStatus::Unread));
+        assert!(items[0].tags.is_empty());
+        assert!(matches!(items[1].status, PocketStatus::Archive));
+        assert_eq!(items[2].tags.to_string(), "Rationality");
+        assert_eq!(items[2].time_added, 1642196007);
+    }
+
+    #[test]
+    fn omnivore() {
+        let metadata = r#"[
+            {"id": "1", "slug": "taoism", "title": "Taoism", "author": null,
+             "url": "https://en.wikipedia.org/wiki/Taoism", "state": "Succeeded",
+             "labels": ["meaning"], "savedAt": "2021-02-23T10:31:39.000Z"},
+            {"id": "2", "slug": "zen", "title": "Zen",
+             "url": "https://en.wikipedia.org/wiki/Zen", "state": "Archived",
+             "labels": [], "savedAt": "2021-02-23T10:31:40.000Z"}
+        ]"#;
+
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        for (name, contents) in [
+            ("metadata_0_to_2.json", metadata),
+            ("content/taoism.html", "<p>not metadata</p>"),
+        ] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("omnivore.zip");
+        fs::write(&path, zip.finish().unwrap().into_inner()).unwrap();
+        let export = OmnivoreReader::from_path(&path).unwrap();
+        let items: Vec<_> = export.items.into_iter().map(|(_, item)| item).collect();
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].tags.to_string(), "meaning");
+        assert_eq!(items[0].time_added, 1614076299);
+        assert!(matches!(items[0].status, PocketStatus::Unread));
+        assert!(matches!(items[1].status, PocketStatus::Archive));
+    }
+}
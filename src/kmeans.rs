@@ -0,0 +1,143 @@
+//! k-means clustering of document embeddings.
+
+use ndarray::{Array1, Array2, Axis};
+use rand::Rng;
+
+/// Gives up on converging after this many rounds of reassigning points.
+const MAX_ITERATIONS: usize = 100;
+
+/// Clusters the rows of `vectors` into at most `k` groups by cosine similarity, returning the
+/// cluster of each row. The starting centroids are picked with k-means++, so the result depends on
+/// `rng`: seed it to get the same clusters for the same vectors.
+pub fn kmeans(vectors: &Array2<f32>, k: usize, rng: &mut impl Rng) -> Vec<usize> {
+    let points = normalized(vectors.clone());
+    let k = k.min(points.nrows());
+    if k == 0 {
+        return vec![];
+    }
+
+    let mut centroids = initial_centroids(&points, k, rng);
+    let mut assignments = vec![usize::MAX; points.nrows()];
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (point, assignment) in points.rows().into_iter().zip(&mut assignments) {
+            let nearest = argmax(&centroids.dot(&point));
+            changed |= nearest != *assignment;
+            *assignment = nearest;
+        }
+
+        if !changed {
+            break;
+        }
+
+        // Move each centroid to the middle of its points. Empty clusters keep theirs.
+        let mut sums = Array2::<f32>::zeros(centroids.raw_dim());
+        for (point, &cluster) in points.rows().into_iter().zip(&assignments) {
+            let mut sum = sums.row_mut(cluster);
+            sum += &point;
+        }
+        for (cluster, sum) in sums.rows().into_iter().enumerate() {
+            if sum.iter().any(|x| *x != 0.0) {
+                centroids.row_mut(cluster).assign(&sum);
+            }
+        }
+        centroids = normalized(centroids);
+    }
+
+    assignments
+}
+
+/// Picks `k` rows of `points` as centroids, each next one likelier the further it is from the
+/// ones picked so far (k-means++).
+fn initial_centroids(points: &Array2<f32>, k: usize, rng: &mut impl Rng) -> Array2<f32> {
+    let mut picked = vec![rng.random_range(0..points.nrows())];
+
+    while picked.len() < k {
+        let centroids = points.select(Axis(0), &picked);
+        let weights: Vec<f32> = points
+            .rows()
+            .into_iter()
+            .map(|point| {
+                let distance = 1.0 - centroids.dot(&point).fold(f32::MIN, |a, &b| a.max(b));
+                distance.max(0.0).powi(2)
+            })
+            .collect();
+
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            // Every point is on top of a centroid already, so any will do.
+            let next = (0..points.nrows()).find(|i| !picked.contains(i));
+            picked.push(next.expect("k is at most the number of points"));
+            continue;
+        }
+
+        let mut target = rng.random::<f32>() * total;
+        let next = weights
+            .iter()
+            .position(|w| {
+                target -= w;
+                target <= 0.0
+            })
+            .unwrap_or(weights.len() - 1);
+        picked.push(next);
+    }
+
+    points.select(Axis(0), &picked)
+}
+
+/// Scales each row to unit length, so dot products are cosine similarities.
+fn normalized(mut vectors: Array2<f32>) -> Array2<f32> {
+    for mut row in vectors.rows_mut() {
+        let norm = row.dot(&row).sqrt();
+        if norm > 0.0 {
+            row /= norm;
+        }
+    }
+
+    vectors
+}
+
+fn argmax(values: &Array1<f32>) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .fold(
+            (0, f32::MIN),
+            |best, (i, &v)| if v > best.1 { (i, v) } else { best },
+        )
+        .0
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    #[test]
+    fn separates_obvious_groups() {
+        let vectors = array![
+            [1.0, 0.1, 0.0],
+            [0.9, 0.0, 0.1],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.1],
+            [0.1, 0.9, 0.0],
+            [0.0, 0.1, 1.0],
+            [0.1, 0.0, 0.9],
+        ];
+
+        let clusters = kmeans(&vectors, 3, &mut StdRng::seed_from_u64(7));
+
+        assert_eq!(clusters[0], clusters[1]);
+        assert_eq!(clusters[0], clusters[2]);
+        assert_eq!(clusters[3], clusters[4]);
+        assert_eq!(clusters[5], clusters[6]);
+        assert_ne!(clusters[0], clusters[3]);
+        assert_ne!(clusters[0], clusters[5]);
+        assert_ne!(clusters[3], clusters[5]);
+
+        assert_eq!(clusters, kmeans(&vectors, 3, &mut StdRng::seed_from_u64(7)));
+    }
+}
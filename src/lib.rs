@@ -1,5 +1,22 @@
+pub mod activity;
+pub mod chunking;
+pub mod clusters;
 pub mod db;
+pub mod digest;
+pub mod embedding;
+pub mod feed;
+pub mod hooks;
+pub mod import;
+pub mod kmeans;
+pub mod limiter;
+#[cfg(feature = "local-embeddings")]
+pub mod local_embedding;
+pub mod offline;
+pub mod opml;
 pub mod pocket;
+pub mod pooling;
+pub mod robots;
+pub mod urls;
 pub mod worker;
 
 pub static USER_AGENT: &str = concat!(
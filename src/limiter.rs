@@ -0,0 +1,66 @@
+//! Per-host request rate limiting for the crawler.
+
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use reqwest::Url;
+use tokio::time::{self, Instant};
+
+/// Spaces out requests to the same host across all workers, while requests to different hosts
+/// go ahead concurrently.
+pub struct HostLimiter {
+    /// Time between two requests to one host, or `None` to not limit at all.
+    interval: Option<Duration>,
+    /// When each host may get its next request.
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl HostLimiter {
+    /// Creates a limiter allowing at most `rps` requests per second to each host. `rps` should be
+    /// above 0, which `--per-host-rps` makes sure of.
+    pub fn new(rps: Option<f64>) -> Self {
+        Self {
+            interval: rps.and_then(|rps| Duration::try_from_secs_f64(1.0 / rps).ok()),
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until we may send `url`'s host another request.
+    pub async fn wait(&self, url: &Url) {
+        let (Some(interval), Some(host)) = (self.interval, url.host_str()) else {
+            return;
+        };
+
+        // Claim the host's next slot and move it along before sleeping, so the lock isn't held
+        // while we wait and other workers can claim the slots after ours.
+        let at = {
+            let mut next = self.next.lock().expect("host limiter lock poisoned");
+            let now = Instant::now();
+            let slot = next.entry(host.to_string()).or_insert(now);
+            let at = (*slot).max(now);
+            *slot = at + interval;
+            at
+        };
+
+        time::sleep_until(at).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spaces_out_requests_per_host() {
+        let limiter = HostLimiter::new(Some(10.0));
+        let a = Url::parse("https://a.example/one").unwrap();
+        let b = Url::parse("https://b.example/one").unwrap();
+
+        let start = Instant::now();
+        limiter.wait(&a).await;
+        limiter.wait(&b).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        limiter.wait(&a).await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,66 @@
+//! Embeddings from a local ONNX model, for when the reading list shouldn't leave the machine.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Result, anyhow};
+use fastembed::{EmbeddingModel, TextEmbedding, TextInitOptions};
+use tokio::task;
+
+use crate::embedding::Embedder;
+
+/// The model we embed with locally unless asked for another: small, fast and English.
+pub const DEFAULT_LOCAL_MODEL: &str = "Xenova/bge-small-en-v1.5";
+
+/// An embedding model running on this machine. The model is downloaded and cached on first use.
+#[derive(Clone)]
+pub struct LocalEmbedder {
+    model: Arc<Mutex<TextEmbedding>>,
+    name: String,
+}
+
+impl LocalEmbedder {
+    /// Loads `model`, by its Hugging Face name (e.g. `Xenova/bge-small-en-v1.5`) or its fastembed
+    /// name (e.g. `BGESmallENV15`).
+    pub fn new(model: &str) -> Result<Self> {
+        let supported = TextEmbedding::list_supported_models();
+        let info = match supported
+            .iter()
+            .find(|info| info.model_code.eq_ignore_ascii_case(model))
+        {
+            Some(info) => info.clone(),
+            None => {
+                let model: EmbeddingModel = model.parse().map_err(|e: String| anyhow!(e))?;
+                TextEmbedding::get_model_info(&model)?.clone()
+            }
+        };
+
+        let embedding = TextEmbedding::try_new(
+            TextInitOptions::new(info.model.clone()).with_show_download_progress(true),
+        )?;
+
+        Ok(Self {
+            model: Arc::new(Mutex::new(embedding)),
+            name: info.model_code,
+        })
+    }
+}
+
+impl Embedder for LocalEmbedder {
+    async fn embed(&self, chunks: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let model = self.model.clone();
+        let chunks: Vec<String> = chunks.iter().map(|c| c.to_string()).collect();
+
+        // Running the model is CPU heavy, so keep it off the async workers.
+        task::spawn_blocking(move || {
+            let mut model = model
+                .lock()
+                .map_err(|_| anyhow!("embedding model poisoned"))?;
+            Ok(model.embed(chunks, None)?)
+        })
+        .await?
+    }
+
+    fn model(&self) -> &str {
+        &self.name
+    }
+}
@@ -1,22 +1,76 @@
-use std::{collections::HashMap, fs::File, iter::zip, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    iter::zip,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{Context, Result, anyhow};
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
-use ndarray::{Array1, Array2, Axis};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use ndarray::Array2;
+use rand::{SeedableRng, rngs::StdRng};
+#[cfg(feature = "local-embeddings")]
+use reading_addiction::local_embedding::{DEFAULT_LOCAL_MODEL, LocalEmbedder};
 use reading_addiction::{
-    USER_AGENT,
-    db::Db,
-    pocket::PocketReader,
-    worker::{WorkItem, spawn_worker},
+    USER_AGENT, activity,
+    chunking::{Chunker, estimate_tokens},
+    clusters::{self, Clusters},
+    db::{
+        CandidateFilter, Db, ExportFilter, HistogramFilter, ItemForChunking, ItemHandle,
+        OverwritePolicy, UrlWithDocVector,
+    },
+    digest::{self, DigestStrategy},
+    embedding::{self, Embedder, OpenRouterEmbedder},
+    feed,
+    hooks::ArchiveHook,
+    import::ImportSource,
+    kmeans::kmeans,
+    limiter::HostLimiter,
+    offline, opml,
+    pocket::{Export, PocketStatus},
+    pooling::{self, Pooling},
+    robots::Robots,
+    worker::{
+        self, Crawled, CrawledArticle, HeadError, HeadFailure, HeadWorkItem, HeadWorkerOutput,
+        RawPage, ReportCard, Retries, WorkItem, spawn_head_worker, spawn_worker,
+    },
+};
+use regex::Regex;
+use reqwest::{Client, ClientBuilder, Proxy, StatusCode, Url};
+use similar::TextDiff;
+use tokio::{
+    signal,
+    sync::mpsc,
+    task::JoinSet,
+    time::{self, MissedTickBehavior},
 };
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use text_splitter::MarkdownSplitter;
-use tokio::{sync::mpsc, task::JoinSet};
 
 const DB_NAME: &str = "addiction.db";
 
+/// How many items an import `--dry-run` shows.
+const DRY_RUN_SAMPLE: usize = 10;
+
+/// How many crawled articles we save per transaction.
+const CRAWL_BATCH: usize = 50;
+
+/// How many pages we fetch at the same time, unless asked otherwise.
+const WORKERS: usize = 16;
+
+/// How many items wait for a worker, and how many results wait to be saved, unless asked
+/// otherwise.
+const QUEUE_SIZE: usize = 64;
+
+/// How many seconds a request may take before we give up on it, unless asked otherwise.
+const TIMEOUT_SECS: u64 = 30;
+
 /// Interact with the reading addiction project.
 #[derive(Debug, Parser)]
 #[command(version, about)]
@@ -24,6 +78,10 @@ struct Cli {
     /// Path to the database [default: addiction.db]
     db: Option<PathBuf>,
 
+    /// print a line for every item crawled or embedded, besides the progress bar
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -32,25 +90,556 @@ struct Cli {
 enum Commands {
     /// parses a Pocket CSV export
     Pocket {
-        /// file path for the Pocket export CSV
+        /// file path for the Pocket export, as CSV, ZIP or JSON
+        path: PathBuf,
+        #[command(flatten)]
+        import: ImportArgs,
+    },
+    /// imports the export of a read-it-later service
+    Import {
+        /// the service the export is from
+        #[arg(value_enum)]
+        source: ImportSource,
+        /// file path for the export
         path: PathBuf,
+        #[command(flatten)]
+        import: ImportArgs,
     },
     /// starts crawl for all items that don't have html yet
     Crawl {
-        /// how many uncrawled items to process [default: all]
-        #[arg(short)]
-        n: Option<usize>,
+        #[command(flatten)]
+        candidates: CandidateArgs,
+        /// only send HEAD requests to record status, content type and size
+        #[arg(long)]
+        head_only: bool,
+        /// only store the raw HTML, leaving extraction for later
+        #[arg(long, conflicts_with = "head_only")]
+        no_extract: bool,
+        /// also crawl items again whose last crawl failed or got very little text, keeping the
+        /// new crawl only if it's better
+        #[arg(long, conflicts_with_all = ["head_only", "no_extract"])]
+        recrawl_failed: bool,
+        #[command(flatten)]
+        fetch: FetchArgs,
+    },
+    /// runs Readability over pages crawled with --no-extract, without fetching them again
+    Extract {
+        #[command(flatten)]
+        candidates: CandidateArgs,
+    },
+    /// runs Readability again over the HTML we fetched, e.g. after it got better at extracting
+    Reparse {
+        #[command(flatten)]
+        candidates: CandidateArgs,
+    },
+    /// crawls specific items again, whether they were crawled before or not
+    CrawlUrls {
+        /// URLs of items in the library
+        #[arg(required = true)]
+        urls: Vec<Url>,
+        /// store the new crawl even if it looks worse than what we have
+        #[arg(long)]
+        overwrite_existing: bool,
+        #[command(flatten)]
+        fetch: FetchArgs,
     },
     /// get latest crawl results as a histogram
-    Histogram,
+    Histogram {
+        #[command(flatten)]
+        filter: HistogramArgs,
+        /// how to print the counts
+        #[arg(long, value_enum, default_value_t = HistogramFormat::Json)]
+        format: HistogramFormat,
+    },
     /// embed articles
     Embed {
-        /// how many articles to embed [default: all]
+        #[command(flatten)]
+        candidates: CandidateArgs,
+        #[command(flatten)]
+        embed: EmbedArgs,
+    },
+    /// embeds articles again, replacing their chunks and document embedding
+    Reembed {
+        /// URLs of items to embed again
+        #[arg(required_unless_present = "stale")]
+        urls: Vec<Url>,
+        /// also embed items again that were crawled after they were last embedded
+        #[arg(long)]
+        stale: bool,
+        #[command(flatten)]
+        embed: EmbedArgs,
+    },
+    /// clusters articles by their doc embedding vector, or prints the vectors for clustering
+    /// elsewhere
+    Cluster {
+        /// run k-means with this many clusters and store the result instead of printing vectors
         #[arg(short)]
-        n: Option<usize>,
+        k: Option<usize>,
+        /// seed for picking the starting centroids, to get the same clusters again [default:
+        /// random, printed]
+        #[arg(long, requires = "k")]
+        seed: Option<u64>,
+        /// only use vectors embedded with this model, needed when articles were embedded with
+        /// more than one
+        #[arg(long)]
+        model: Option<String>,
+    },
+    /// scales all stored article embeddings to unit length, as `embed --normalize` stores them
+    NormalizeVectors,
+    /// lists the articles most similar to an article
+    Related {
+        /// URL of the article
+        url: Url,
+        /// how many articles to list
+        #[arg(short, default_value_t = 5)]
+        n: usize,
+        /// leave out articles less similar than this (cosine similarity of the doc vectors)
+        #[arg(long, default_value_t = 0.6)]
+        min_similarity: f32,
+    },
+    /// picks unread articles that fit in a reading session
+    Digest {
+        /// how many minutes of reading to pick
+        minutes: u32,
+        /// which articles to consider first
+        #[arg(long, value_enum, default_value_t = DigestStrategy::Oldest)]
+        strategy: DigestStrategy,
+    },
+    /// exports the reading list for other tools
+    Export {
+        /// the format to export to
+        #[arg(long, value_enum, default_value_t = ExportFormat::Opml)]
+        format: ExportFormat,
+        /// only export items with this status
+        #[arg(long, value_parser = ["unread", "archive", "all"], default_value = "all")]
+        status: String,
+        /// only export items saved on or after this day (YYYY-MM-DD, UTC) or in this many recent
+        /// days or weeks (e.g. 7d, 2w)
+        #[arg(long, value_parser = parse_since)]
+        since: Option<i64>,
+        /// only export items whose URL matches this regex
+        #[arg(long)]
+        url_pattern: Option<Regex>,
+        /// file to write the export to [default: stdout]
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// writes every crawled article to a standalone HTML page, with an index page linking them
+    ExportStatic {
+        /// directory to write the pages to, created if it doesn't exist
+        out_dir: PathBuf,
+        /// only export articles saved on or after this day (YYYY-MM-DD, UTC) or in this many
+        /// recent days or weeks (e.g. 7d, 2w)
+        #[arg(long, value_parser = parse_since)]
+        since: Option<i64>,
     },
-    /// get URLs and their doc embedding vector
-    Cluster,
+    /// lists all tags with how many items have them, or the items with one tag
+    Tags {
+        /// list the items with this tag
+        tag: Option<String>,
+    },
+    /// prints aggregate numbers about the library
+    Stats {
+        #[command(flatten)]
+        filter: HistogramArgs,
+        /// print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// lists the most recent crawl failures
+    Errors {
+        /// how many failures to list
+        #[arg(short, default_value_t = 20)]
+        n: usize,
+    },
+    /// shows what we know about an item
+    Show {
+        /// URL or id of the item
+        item: String,
+    },
+    /// lists the links in an article, marking the ones already on the reading list
+    Links {
+        /// URL of the article
+        url: Url,
+    },
+    /// adds a URL to the reading list
+    Save {
+        url: Url,
+        /// title until a crawl finds a better one [default: the URL]
+        #[arg(long)]
+        title: Option<String>,
+    },
+    /// marks an item as read
+    Archive {
+        url: Url,
+        /// move it back to unread instead
+        #[arg(long)]
+        undo: bool,
+    },
+    /// prints the URLs of the next unread items, e.g. to open them in a browser
+    Queue {
+        /// how many items to queue up
+        #[arg(short, default_value_t = 10)]
+        n: usize,
+        /// which items come first
+        #[arg(long, value_enum, default_value_t = DigestStrategy::Oldest)]
+        strategy: DigestStrategy,
+    },
+    /// checks whether the links of archived items still work
+    Verify {
+        #[command(flatten)]
+        candidates: CandidateArgs,
+        #[command(flatten)]
+        fetch: FetchArgs,
+    },
+    /// shows what changed in an article between its last two crawls
+    Diff {
+        /// URL of the article
+        url: Url,
+    },
+    /// summarizes reading activity and streaks
+    Activity {
+        /// how many of the most recent active days to list
+        #[arg(long, default_value_t = 14)]
+        days: usize,
+    },
+    /// tags untagged articles after the cluster they're in
+    LabelClusters {
+        /// JSON file mapping cluster ids to member URLs, as written by the clustering scripts
+        /// [default: the clusters stored by `cluster -k`]
+        clusters: Option<PathBuf>,
+        /// JSON file mapping cluster ids to labels [default: derived from tags and titles]
+        #[arg(long)]
+        labels: Option<PathBuf>,
+        /// remove all cluster tags instead
+        #[arg(long, conflicts_with_all = ["clusters", "labels"])]
+        remove: bool,
+    },
+    /// merges the items of another database into this one
+    Merge {
+        /// file path for the other database
+        other: PathBuf,
+    },
+    /// merges items saved more than once under slightly different URLs
+    Dedup,
+    /// compacts the database file and refreshes its indexes, e.g. after lots of recrawls
+    Vacuum,
+    /// searches the title and text of crawled articles
+    Search {
+        /// FTS5 query, e.g. `rust AND async` or `"reading list"`
+        query: String,
+        /// how many results to show
+        #[arg(short, default_value_t = 20)]
+        n: usize,
+    },
+    /// searches the embedded articles for the ones closest in meaning to a query
+    SemanticSearch {
+        /// what to look for, in plain words
+        query: String,
+        /// how many results to show
+        #[arg(short, default_value_t = 10)]
+        n: usize,
+        #[command(flatten)]
+        embedder: EmbedderArgs,
+    },
+}
+
+/// Flags that narrow down which items a command works on.
+#[derive(Debug, Args)]
+struct CandidateArgs {
+    /// how many items to process [default: all]
+    #[arg(short)]
+    n: Option<usize>,
+    /// process this many randomly picked items instead of the first ones
+    #[arg(long, conflicts_with = "n")]
+    sample: Option<usize>,
+    /// only process items whose URL matches this regex
+    #[arg(long)]
+    url_pattern: Option<Regex>,
+    /// only process items with this tag
+    #[arg(long)]
+    only_tag: Option<String>,
+    /// only process items with this status
+    #[arg(long, value_parser = ["unread", "archive"])]
+    status: Option<String>,
+    /// only process items saved on or after this day (YYYY-MM-DD, UTC) or in this many recent
+    /// days or weeks (e.g. 7d, 2w)
+    #[arg(long, value_parser = parse_since)]
+    since: Option<i64>,
+}
+
+/// Parses a count that has to be at least 1, like the number of workers.
+fn at_least_one(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(0) => Err("should be at least 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(_) => Err(format!("{value:?} is not a whole number")),
+    }
+}
+
+/// Parses a rate that has to be above 0, like requests per second.
+fn positive_rate(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        Ok(_) => Err("should be a number above 0".to_string()),
+        Err(_) => Err(format!("{value:?} is not a number")),
+    }
+}
+
+/// Parses a `--since` flag into a Unix timestamp: a day (YYYY-MM-DD, UTC), or a number of days or
+/// weeks back from now (e.g. `7d`, `2w`).
+fn parse_since(value: &str) -> Result<i64, String> {
+    if let Ok(day) = value.parse::<NaiveDate>() {
+        return Ok(day.and_time(NaiveTime::MIN).and_utc().timestamp());
+    }
+
+    let (n, days) = if let Some(n) = value.strip_suffix('d') {
+        (n, 1)
+    } else if let Some(n) = value.strip_suffix('w') {
+        (n, 7)
+    } else {
+        return Err("expected a day like 2025-11-23 or a span like 7d or 2w".to_string());
+    };
+    let n: u32 = n
+        .parse()
+        .map_err(|_| format!("{n:?} is not a whole number of days or weeks"))?;
+
+    Ok(Utc::now().timestamp() - i64::from(n) * days * 24 * 60 * 60)
+}
+
+impl From<CandidateArgs> for CandidateFilter {
+    fn from(args: CandidateArgs) -> Self {
+        Self {
+            limit: args.n.or(args.sample),
+            url_pattern: args.url_pattern,
+            tag: args.only_tag,
+            status: args.status,
+            since: args.since,
+            random: args.sample.is_some(),
+        }
+    }
+}
+
+/// Flags that narrow down which items a count covers.
+#[derive(Debug, Args)]
+struct HistogramArgs {
+    /// only count items last crawled on or after this day (YYYY-MM-DD, UTC)
+    #[arg(long)]
+    since: Option<NaiveDate>,
+    /// only count items last crawled on or before this day (YYYY-MM-DD, UTC)
+    #[arg(long)]
+    until: Option<NaiveDate>,
+    /// only count items with this status
+    #[arg(long, value_parser = ["unread", "archive"])]
+    status: Option<String>,
+}
+
+impl From<HistogramArgs> for HistogramFilter {
+    fn from(args: HistogramArgs) -> Self {
+        let start_of = |day: NaiveDate| day.and_time(NaiveTime::MIN).and_utc().timestamp();
+        Self {
+            since: args.since.map(start_of),
+            until: args.until.and_then(|day| day.succ_opt()).map(start_of),
+            status: args.status,
+        }
+    }
+}
+
+/// Flags for how an export is imported.
+#[derive(Debug, Args)]
+struct ImportArgs {
+    /// skip this many rows, e.g. to resume an import that died halfway
+    #[arg(long, default_value_t = 0)]
+    resume_from: usize,
+    /// skip items we already have, leaving their title, tags and status alone
+    #[arg(long)]
+    skip_existing: bool,
+    /// only report what the import would do, without saving anything
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Flags for how articles are chunked and embedded.
+#[derive(Debug, Args)]
+struct EmbedArgs {
+    /// at most this many characters per chunk
+    #[arg(long, default_value_t = 5000)]
+    chunk_size: usize,
+    /// how many characters consecutive chunks share
+    #[arg(long, default_value_t = 0)]
+    chunk_overlap: usize,
+    /// at most this many requests per second to the embedding provider [default: no limit]
+    #[arg(long)]
+    embed_rps: Option<f64>,
+    /// skip articles with a chunk over the embedding model's context window
+    #[arg(long, default_value_t = 32_000)]
+    max_tokens: usize,
+    /// prepend the nearest preceding heading to each chunk
+    #[arg(long)]
+    heading_context: bool,
+    /// how many articles to embed at the same time
+    #[arg(long, default_value_t = 4, value_parser = at_least_one)]
+    concurrency: usize,
+    #[command(flatten)]
+    embedder: EmbedderArgs,
+    /// how chunk embeddings become the article's embedding
+    #[arg(long, value_enum, default_value_t = Pooling::Mean)]
+    pooling: Pooling,
+    /// scale the article's embedding to unit length (chunk embeddings are stored as the model
+    /// returns them)
+    #[arg(long)]
+    normalize: bool,
+}
+
+/// Flags for which embedding model to use and where it runs.
+#[derive(Debug, Args)]
+struct EmbedderArgs {
+    /// where the embedding model runs
+    #[arg(long, value_enum, default_value_t = EmbedBackend::OpenRouter)]
+    backend: EmbedBackend,
+    /// embedding model to use [default: qwen/qwen3-embedding-8b on OpenRouter,
+    /// Xenova/bge-small-en-v1.5 locally]
+    #[arg(long)]
+    model: Option<String>,
+}
+
+/// Flags for how crawls fetch pages.
+#[derive(Debug, Args)]
+struct FetchArgs {
+    /// retries after DNS failures, connection resets and timeouts
+    #[arg(long, default_value_t = 2)]
+    network_retries: u32,
+    /// retries after 5xx and 429 responses
+    #[arg(long, default_value_t = 2)]
+    server_retries: u32,
+    /// milliseconds to wait before the first retry, doubling with every retry after it up to a
+    /// minute
+    #[arg(long, default_value_t = 1000)]
+    retry_delay_ms: u64,
+    /// at most this many requests per second to any one host [default: no limit]
+    #[arg(long, value_parser = positive_rate)]
+    per_host_rps: Option<f64>,
+    /// give up on a request that takes longer than this many seconds, from connecting until the
+    /// last byte
+    #[arg(long, default_value_t = TIMEOUT_SECS)]
+    timeout_secs: u64,
+    /// send this User-Agent instead of our own, for sites that turn away bots
+    #[arg(long)]
+    user_agent: Option<String>,
+    /// send all requests through this HTTP(S) proxy, e.g. http://localhost:8080
+    #[arg(long)]
+    proxy: Option<Url>,
+    /// how many pages to fetch at the same time
+    #[arg(long, default_value_t = WORKERS, value_parser = at_least_one)]
+    workers: usize,
+    /// how many items wait for a free worker, and how many results wait to be saved
+    #[arg(long, default_value_t = QUEUE_SIZE, value_parser = at_least_one)]
+    queue_size: usize,
+}
+
+impl FetchArgs {
+    /// Starts building an HTTP client with our User-Agent, proxy and timeout.
+    fn client_builder(&self) -> Result<ClientBuilder> {
+        let mut builder = Client::builder()
+            .user_agent(self.user_agent.as_deref().unwrap_or(USER_AGENT))
+            .timeout(Duration::from_secs(self.timeout_secs));
+
+        if let Some(proxy) = &self.proxy {
+            // reqwest would take other schemes too, and then fail every request.
+            if !matches!(proxy.scheme(), "http" | "https") {
+                return Err(anyhow!("proxy {proxy} should be an http(s) URL"));
+            }
+            let proxy =
+                Proxy::all(proxy.clone()).with_context(|| format!("invalid proxy {proxy}"))?;
+            builder = builder.proxy(proxy);
+        }
+
+        Ok(builder)
+    }
+
+    fn retries(&self) -> Retries {
+        Retries {
+            network: self.network_retries,
+            server: self.server_retries,
+            base_delay: Duration::from_millis(self.retry_delay_ms),
+        }
+    }
+}
+
+/// Formats we can export the reading list to.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    /// OPML 2.0, with an outline per item
+    Opml,
+    /// JSON Lines, one object per item with its markdown and doc vector, for other tools to
+    /// analyze the articles
+    Jsonl,
+    /// an Atom feed of the 50 most recently saved items, to follow the list from a feed reader
+    Atom,
+}
+
+/// Where articles get embedded.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum EmbedBackend {
+    /// OpenRouter's API, which needs OPENROUTER_API_KEY
+    #[value(name = "openrouter")]
+    OpenRouter,
+    /// an ONNX model on this machine (needs the `local-embeddings` feature)
+    Local,
+}
+
+/// Output formats for the crawl status histogram.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum HistogramFormat {
+    /// a JSON map of status to count
+    Json,
+    /// `status,count` rows, sorted by status
+    Csv,
+    /// aligned columns, sorted by status
+    Table,
+}
+
+/// Shows how far along a batch of items we are, and a line per item if we're verbose.
+struct Progress {
+    bar: ProgressBar,
+    verbose: bool,
+}
+
+impl Progress {
+    /// Creates a progress bar over `len` items, counting them as `done` (e.g. "crawled").
+    fn new(len: usize, done: &str, verbose: bool) -> Self {
+        let style = ProgressStyle::with_template(&format!(
+            "{{pos}}/{{len}} {done} [{{elapsed_precise}}] {{wide_bar}} eta {{eta}}"
+        ))
+        .expect("progress bar template is valid");
+
+        Self {
+            bar: ProgressBar::new(len as u64).with_style(style),
+            verbose,
+        }
+    }
+
+    /// Prints a line about an item, if we're verbose.
+    fn item(&self, line: impl fmt::Display) {
+        if self.verbose {
+            self.bar.suspend(|| println!("{line}"));
+        }
+    }
+
+    /// Prints an error, which is worth seeing even if we're not verbose.
+    fn error(&self, line: impl fmt::Display) {
+        self.bar.suspend(|| eprintln!("{line}"));
+    }
+
+    /// Counts an item as done.
+    fn inc(&self) {
+        self.bar.inc(1);
+    }
+
+    /// Clears the bar, so whatever is printed next doesn't end up above it.
+    fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
 }
 
 #[tokio::main]
@@ -59,151 +648,653 @@ async fn main() -> Result<()> {
 
     // Set up our database.
     let db_path = cli.db.unwrap_or(PathBuf::from(DB_NAME.to_string()));
-    let db = Db::new(db_path).await?;
+    let db = Db::new(db_path.clone()).await?;
 
     // Do what was asked.
     match cli.command {
-        Some(Commands::Pocket { path }) => {
-            let f = File::open(path)?;
-            let pr = PocketReader::new(f);
-            let items = pr.read()?;
-            println!("found {} Pocket items", items.len());
-
-            for item in items {
-                let url = item.url.to_string();
-                if db.save_item(item).await.is_err() {
-                    return Err(anyhow!("failed to insert item for {url}..."));
-                }
-            }
-        }
-        Some(Commands::Crawl { n }) => {
-            // Create channel for distributing work items.
-            let (work_q, r) = async_channel::bounded(64);
+        Some(Commands::Pocket { path, import }) => {
+            let export = ImportSource::Pocket.read(path)?;
+            println!("found {} Pocket items", export.items.len());
 
-            // Create an HTTP client that can be shared (internal connection pool).
-            let client = Client::builder().user_agent(USER_AGENT).build()?;
+            import_items(&db, export, &import).await?;
+        }
+        Some(Commands::Import {
+            source,
+            path,
+            import,
+        }) => {
+            let export = source.read(path)?;
+            println!("found {} items", export.items.len());
 
-            // Spawn a pool of worker tasks for crawling and cleaning.
-            let mut workers = JoinSet::new();
-            for _ in 0..16 {
-                let r_i = r.clone();
-                let c_i = client.clone();
-                workers.spawn(async move { spawn_worker(c_i, r_i).await });
+            import_items(&db, export, &import).await?;
+        }
+        Some(Commands::Crawl {
+            candidates,
+            head_only: true,
+            fetch,
+            ..
+        }) => {
+            let skipped = db.skip_unsupported_schemes().await?;
+            if skipped > 0 {
+                println!("Skipping {skipped} items that don't have an http(s) URL");
             }
 
-            let candidates = db.get_uncrawled_items(n).await?;
-            println!("Found {} candidates for crawling", candidates.len());
-
-            // Results channel for work output
-            let (results_tx, mut results_rx) = mpsc::channel(64);
-
-            let worker_tx = results_tx.clone();
-
-            // Spawn a Seeder task so we can start consuming results while
-            // we're still pushing work on the queue.
-            tokio::spawn(async move {
-                for c in candidates {
-                    let _ = work_q
-                        .send(WorkItem {
-                            url: c.url,
-                            circle_back: worker_tx.clone(),
-                        })
-                        .await;
-                }
-            });
+            let candidates = db.get_uncrawled_items(candidates.into()).await?;
+            println!("Found {} candidates for checking", candidates.len());
 
-            // Prevent that we keep one sender open!
-            drop(results_tx);
+            let mut results_rx = head_check_all(&fetch, candidates)?;
 
             while let Some(worker_output) = results_rx.recv().await {
                 match worker_output {
-                    Ok(article) => {
-                        // Update our database with the extracted content
+                    // These servers don't do HEAD, so we didn't learn anything.
+                    Ok(check)
+                        if check.status == StatusCode::METHOD_NOT_ALLOWED
+                            || check.status == StatusCode::NOT_IMPLEMENTED =>
+                    {
+                        println!("{} - {} (HEAD not supported)", check.status, check.url);
+                    }
+                    Ok(check) => {
                         println!(
-                            "{} - {} {} bytes of text, ~{} tokens",
-                            article.status,
-                            article.url,
-                            article.markdown.len(),
-                            article.markdown.len() / 4
+                            "{} - {} {} {} bytes",
+                            check.status,
+                            check.url,
+                            check.content_type.as_deref().unwrap_or("?"),
+                            check
+                                .content_length
+                                .map_or("?".to_string(), |l| l.to_string())
                         );
-                        db.save_crawl(article).await?;
+                        db.save_head_check(check).await?;
                     }
                     Err(err) => eprintln!("Worker error: {err}"),
                 }
             }
+        }
+        Some(Commands::Crawl {
+            candidates,
+            no_extract,
+            recrawl_failed,
+            fetch,
+            ..
+        }) => {
+            let skipped = db.skip_unsupported_schemes().await?;
+            if skipped > 0 {
+                println!("Skipping {skipped} items that don't have an http(s) URL");
+            }
+
+            let filter = CandidateFilter::from(candidates);
+
+            let candidates = db.get_uncrawled_items(filter.clone()).await?;
+            println!("Found {} candidates for crawling", candidates.len());
+
+            let interrupted = crawl(
+                &db,
+                candidates,
+                OverwritePolicy::Always,
+                &fetch,
+                !no_extract,
+                cli.verbose,
+            )
+            .await?;
+
+            if recrawl_failed && !interrupted {
+                let candidates = db.get_recrawlable_items(filter).await?;
+                println!("Found {} failed or short crawls to retry", candidates.len());
+
+                crawl(
+                    &db,
+                    candidates,
+                    OverwritePolicy::IfNotWorse,
+                    &fetch,
+                    true,
+                    cli.verbose,
+                )
+                .await?;
+            }
+        }
+        Some(Commands::Extract { candidates }) => {
+            let pages = db.get_unextracted_items(candidates.into()).await?;
+            println!("Found {} pages to extract", pages.len());
 
-            // Wait for our full worker pool to finish cleaning up.
-            let _report_cards = workers.join_all().await;
+            extract_all(&db, pages).await?;
         }
-        Some(Commands::Histogram) => {
-            let hist: HashMap<u16, usize> = db
-                .get_crawl_status_hist()
+        Some(Commands::Reparse { candidates }) => {
+            let pages = db.get_reparsable_items(candidates.into()).await?;
+            println!("Found {} pages to extract again", pages.len());
+
+            extract_all(&db, pages).await?;
+        }
+        Some(Commands::CrawlUrls {
+            urls,
+            overwrite_existing,
+            fetch,
+        }) => {
+            let policy = if overwrite_existing {
+                OverwritePolicy::Always
+            } else {
+                OverwritePolicy::IfNotWorse
+            };
+
+            let items = urls.into_iter().map(|url| ItemHandle { url }).collect();
+            crawl(&db, items, policy, &fetch, true, cli.verbose).await?;
+        }
+        Some(Commands::Histogram { filter, format }) => {
+            let hist: BTreeMap<u16, usize> = db
+                .get_crawl_status_hist(filter.into())
                 .await?
                 .into_iter()
                 .map(|(k, v)| (k.unwrap_or(0), v))
                 .collect();
 
-            println!("{}", serde_json::to_string(&hist)?);
+            match format {
+                HistogramFormat::Json => println!("{}", serde_json::to_string(&hist)?),
+                HistogramFormat::Csv => {
+                    let mut writer = csv::Writer::from_writer(io::stdout());
+                    writer.write_record(["status", "count"])?;
+                    for (status, count) in hist {
+                        writer.serialize((status, count))?;
+                    }
+                    writer.flush()?;
+                }
+                HistogramFormat::Table => {
+                    let width = hist
+                        .values()
+                        .max()
+                        .map_or(5, |max| max.to_string().len().max(5));
+                    println!("status  {:>width$}", "count");
+                    for (status, count) in hist {
+                        println!("{status:>6}  {count:>width$}");
+                    }
+                }
+            }
         }
-        Some(Commands::Embed { n }) => {
-            let candidates = db.get_unembedded_items(n).await?;
+        Some(Commands::Embed { candidates, embed }) => {
+            let candidates = db.get_unembedded_items(candidates.into()).await?;
             println!("Found {} candidates for embedding", candidates.len());
 
-            let api_key = std::env::var("OPENROUTER_API_KEY")?;
+            embed_all(&db, candidates, &embed, cli.verbose).await?;
+        }
+        Some(Commands::Reembed { urls, stale, embed }) => {
+            let mut urls = urls;
+            if stale {
+                urls.extend(db.get_stale_embeddings().await?);
+            }
 
-            // Create an HTTP client that can be shared (internal connection pool).
-            let client = Client::new();
+            // The old embeddings stay until the new ones are in, in case embedding fails.
+            let items = db.get_items_for_chunking(urls.clone()).await?;
+            let found: HashSet<&Url> = items.iter().map(|item| &item.url).collect();
+            for url in urls.iter().filter(|url| !found.contains(url)) {
+                println!("{url} is not on the reading list or hasn't been crawled");
+            }
+            println!("Found {} articles to embed again", items.len());
 
-            // Create our semantic chunker for markdown with a high max because
-            // we're using our embeddings for clustering and not for retrieval.
-            // That's why we can be less precise.
-            let splitter = MarkdownSplitter::new(5000);
+            embed_all(&db, items, &embed, cli.verbose).await?;
+        }
+        Some(Commands::Cluster { k: None, model, .. }) => {
+            let items = db.get_urls_with_doc_vector(model).await?;
+            check_single_model(&items)?;
+            println!("{}", serde_json::to_string(&items)?);
+        }
+        Some(Commands::Cluster {
+            k: Some(k),
+            seed,
+            model,
+        }) => {
+            let items = db.get_urls_with_doc_vector(model).await?;
+            check_single_model(&items)?;
+            let Some(dim) = items.first().map(|item| item.vector.len()) else {
+                println!("No embedded articles to cluster");
+                return Ok(());
+            };
+
+            let (urls, vectors): (Vec<_>, Vec<_>) = items
+                .into_iter()
+                .filter(|item| item.vector.len() == dim)
+                .map(|item| (item.url, item.vector))
+                .unzip();
+            let vectors = Array2::from_shape_vec((urls.len(), dim), vectors.concat())?;
 
-            // Ugh, okay, don't have the mental capacity right now to do this with concurrent actors.
-            // So let's just do it in serial.
-            for c in candidates {
-                let chunks: Vec<&str> = splitter.chunks(&c.markdown).collect();
+            let seed = seed.unwrap_or_else(rand::random);
+            println!("Clustering {} articles with seed {seed}", urls.len());
+            let clusters = kmeans(&vectors, k, &mut StdRng::seed_from_u64(seed));
+
+            let mut sizes = vec![0; k];
+            for cluster in &clusters {
+                sizes[*cluster] += 1;
+            }
+            for (cluster, size) in sizes.iter().enumerate() {
+                println!("cluster {cluster}: {size} articles");
+            }
+
+            db.save_cluster_assignments(zip(urls, clusters).collect())
+                .await?;
+        }
+        Some(Commands::Related {
+            url,
+            n,
+            min_similarity,
+        }) => {
+            let similar = db.get_similar(url, n, min_similarity).await?;
+            if similar.is_empty() {
+                println!("No closely related articles");
+            }
+
+            for item in similar {
+                println!("{:.2}  {}\n      {}", item.similarity, item.title, item.url);
+            }
+        }
+        Some(Commands::Digest { minutes, strategy }) => {
+            let candidates = db.get_digest_candidates(strategy).await?;
+            let digest = digest::select(candidates, minutes);
+
+            for item in &digest {
+                println!(
+                    "{:>3.0} min  {}\n          {}",
+                    item.minutes.ceil(),
+                    item.title,
+                    item.url
+                );
+            }
+
+            let total: f64 = digest.iter().map(|item| item.minutes).sum();
+            println!("{} articles, ~{:.0} minutes", digest.len(), total.ceil());
+        }
+        Some(Commands::Export {
+            format: ExportFormat::Opml,
+            status,
+            since,
+            url_pattern,
+            out,
+        }) => {
+            let status = (status != "all").then_some(status);
+            let filter = ExportFilter {
+                status: status.clone(),
+                since,
+                url_pattern,
+            };
+            let items = db.get_list_items(filter).await?;
+            let title = match status {
+                Some(status) => format!("Reading list ({status})"),
+                None => "Reading list".to_string(),
+            };
+            let opml = opml::to_opml(&title, &items);
+
+            match out {
+                Some(path) => {
+                    fs::write(&path, opml)?;
+                    println!("Exported {} items to {}", items.len(), path.display());
+                }
+                None => print!("{opml}"),
+            }
+        }
+        Some(Commands::Export {
+            format: ExportFormat::Jsonl,
+            status,
+            since,
+            url_pattern,
+            out,
+        }) => {
+            let status = (status != "all").then_some(status);
+            let filter = ExportFilter {
+                status: status.clone(),
+                since,
+                url_pattern,
+            };
+            let mut writer: Box<dyn Write> = match &out {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+
+            let mut items = db.stream_all_items(filter);
+            let mut exported = 0;
+            while let Some(item) = items.recv().await {
+                serde_json::to_writer(&mut writer, &item?)?;
+                writer.write_all(b"\n")?;
+                exported += 1;
+            }
+            writer.flush()?;
+
+            if let Some(path) = out {
+                println!("Exported {exported} items to {}", path.display());
+            }
+        }
+        Some(Commands::Export {
+            format: ExportFormat::Atom,
+            status,
+            since,
+            url_pattern,
+            out,
+        }) => {
+            let status = (status != "all").then_some(status);
+            let filter = ExportFilter {
+                status: status.clone(),
+                since,
+                url_pattern,
+            };
+            let items = db.get_feed_items(filter).await?;
+            let title = match status {
+                Some(status) => format!("Reading list ({status})"),
+                None => "Reading list".to_string(),
+            };
+            let atom = feed::to_atom(&title, &items);
+
+            match out {
+                Some(path) => {
+                    fs::write(&path, atom)?;
+                    println!("Exported {} items to {}", items.len(), path.display());
+                }
+                None => print!("{atom}"),
+            }
+        }
+        Some(Commands::ExportStatic { out_dir, since }) => {
+            let articles = db.get_offline_articles(since).await?;
+            fs::create_dir_all(&out_dir)?;
+
+            for article in &articles {
+                fs::write(
+                    out_dir.join(article.file_name()),
+                    offline::render_article(article),
+                )?;
+            }
+            fs::write(out_dir.join("index.html"), offline::render_index(&articles))?;
+
+            println!(
+                "Exported {} articles to {}",
+                articles.len(),
+                out_dir.display()
+            );
+        }
+        Some(Commands::Tags { tag: None }) => {
+            for (tag, count) in db.get_tag_counts().await? {
+                println!("{count:>5}  {tag}");
+            }
+        }
+        Some(Commands::Tags { tag: Some(tag) }) => {
+            for item in db.get_items_by_tag(tag).await? {
+                println!("{} - {}", item.title, item.url);
+            }
+        }
+        Some(Commands::Stats { filter, json }) => {
+            let stats = db.get_library_stats(filter.into()).await?;
+            if json {
+                println!("{}", serde_json::to_string(&stats)?);
+                return Ok(());
+            }
+
+            let share = |n: usize| {
+                if stats.total == 0 {
+                    String::new()
+                } else {
+                    format!(" ({:.0}%)", 100.0 * n as f64 / stats.total as f64)
+                }
+            };
+
+            println!("items               {}", stats.total);
+            for (status, count) in &stats.by_status {
+                println!("  {status:<18}{count}{}", share(*count));
+            }
+            println!(
+                "with text           {}{}",
+                stats.with_markdown,
+                share(stats.with_markdown)
+            );
+            println!(
+                "embedded            {}{}",
+                stats.embedded,
+                share(stats.embedded)
+            );
+            println!("words               {}", stats.total_words);
+            if let Some(minutes) = stats.avg_reading_minutes {
+                println!("avg reading time    {minutes:.1} min");
+            }
+            println!(
+                "crawled             {}{}",
+                stats.crawled,
+                share(stats.crawled)
+            );
+            if let Some(rate) = stats.crawl_success_rate {
+                println!("crawl success rate  {:.0}%", 100.0 * rate);
+            }
+            println!(
+                "paywalled           {}{}",
+                stats.paywalled,
+                share(stats.paywalled)
+            );
+        }
+        Some(Commands::Errors { n }) => {
+            for err in db.get_crawl_errors(n).await? {
+                let at = DateTime::from_timestamp(err.at, 0).map_or(err.at.to_string(), |dt| {
+                    dt.format("%Y-%m-%d %H:%M").to_string()
+                });
+                println!("{at}  {}\n    {}", err.url, err.message);
+            }
+        }
+        Some(Commands::Show { item }) => {
+            // Saved URLs are stored the way `Url` formats them.
+            let key = Url::parse(&item).map_or(item.clone(), |url| url.to_string());
+            let meta = db
+                .get_item_meta(key)
+                .await?
+                .ok_or_else(|| anyhow!("{item} is not on the reading list"))?;
 
-                let req = EmbeddingRequest {
-                    model: "qwen/qwen3-embedding-8b".to_string(),
-                    input: chunks.clone(),
+            let date = |ts: i64| {
+                DateTime::from_timestamp(ts, 0)
+                    .map_or(ts.to_string(), |dt| dt.format("%Y-%m-%d").to_string())
+            };
+
+            println!("{}\n{}", meta.title, meta.url);
+            println!("id         {}", meta.pub_id);
+            if let Some(final_url) = meta.final_url.filter(|u| *u != meta.url) {
+                println!("moved to   {final_url}");
+            }
+            if let Some(author) = meta.author {
+                println!("by         {author}");
+            }
+            if let Some(published) = meta.published_at {
+                println!("published  {}", date(published));
+            }
+            println!("saved      {} ({})", date(meta.time_added), meta.status);
+            if !meta.tags.is_empty() {
+                println!("tags       {}", meta.tags.join(", "));
+            }
+            match (meta.time_last_crawl, meta.http_status) {
+                (Some(crawled), Some(status)) => {
+                    println!("crawled    {} ({status})", date(crawled))
+                }
+                (Some(crawled), None) => println!("crawled    {}", date(crawled)),
+                (None, _) => println!("crawled    never"),
+            }
+            if meta.paywalled {
+                println!("paywalled  probably, we only got a teaser");
+            }
+        }
+        Some(Commands::Links { url }) => {
+            let saved = db.get_all_urls().await?;
+
+            for link in db.get_links(url).await? {
+                let marker = if saved.contains(&link.href) { "+" } else { " " };
+                println!("{marker} {}\n    {}", link.text, link.href);
+            }
+        }
+        Some(Commands::Save { url, title }) => {
+            let title = title.unwrap_or_else(|| url.to_string());
+            if db.save_url(url.clone(), title).await? {
+                println!("saved {url}");
+            } else {
+                println!("{url} is already on the reading list");
+            }
+        }
+        Some(Commands::Archive { url, undo }) => {
+            let status = if undo {
+                PocketStatus::Unread
+            } else {
+                PocketStatus::Archive
+            };
+
+            let Some(changed) = db.set_status(url.clone(), status).await? else {
+                return Err(anyhow!("{url} is not on the reading list"));
+            };
+            if undo {
+                println!("moved {url} back to unread");
+            } else {
+                println!("archived {url}");
+            }
+
+            if changed
+                && !undo
+                && let Some(hook) = archive_hook()?
+                && let Some(meta) = db.get_item_meta(url.to_string()).await?
+            {
+                // The same shape as the items an import archives.
+                let payload = serde_json::json!({
+                    "title": meta.title,
+                    "url": meta.url,
+                    "time_added": meta.time_added,
+                    "tags": meta.tags,
+                    "status": meta.status,
+                });
+                if let Err(err) = hook.notify(&payload).await {
+                    eprintln!("archive hook failed for {url}: {err}");
+                }
+            }
+        }
+        Some(Commands::Queue { n, strategy }) => {
+            for url in db.get_unread_queue(n, strategy).await? {
+                println!("{url}");
+            }
+        }
+        Some(Commands::Verify { candidates, fetch }) => {
+            let candidates = db.get_archived_items(candidates.into()).await?;
+            let total = candidates.len();
+            println!("Found {total} archived items to verify");
+
+            let mut results_rx = head_check_all(&fetch, candidates)?;
+
+            let mut dead = 0;
+            while let Some(worker_output) = results_rx.recv().await {
+                // Server errors and network failures were retried already, so if they're still
+                // there the link is as good as dead. Anything else but gone means it's alive.
+                let (url, is_dead) = match worker_output {
+                    Ok(check) => {
+                        println!("{} - {}", check.status, check.url);
+                        let is_dead =
+                            matches!(check.status, StatusCode::NOT_FOUND | StatusCode::GONE);
+                        (check.url, is_dead)
+                    }
+                    Err(
+                        err @ HeadError {
+                            cause: HeadFailure::Disallowed,
+                            ..
+                        },
+                    ) => {
+                        println!("{err}, skipping it");
+                        continue;
+                    }
+                    Err(err) => {
+                        eprintln!("{err}");
+                        (err.url, true)
+                    }
                 };
 
-                let res = client
-                    .post("https://openrouter.ai/api/v1/embeddings")
-                    .header("Authorization", format!("Bearer {}", &api_key))
-                    .header("Content-Type", "application/json")
-                    .json(&req)
-                    .send()
-                    .await?;
-
-                println!("{} (OpenRouter) - {}", res.status(), c.url);
-
-                let embedding: EmbeddingResponse =
-                    res.json().await.context("failed to parse response")?;
-
-                let mut data = embedding.data;
-                data.sort_by_key(|d| d.index);
-
-                for (chunk_text, chunk_data) in zip(chunks, data.clone()) {
-                    db.save_chunk_and_embedding(
-                        c.url.clone(),
-                        chunk_text.to_string(),
-                        &chunk_data.embedding,
-                    )
-                    .await?;
+                dead += usize::from(is_dead);
+                db.save_link_dead(url, is_dead).await?;
+            }
+
+            println!("{dead} of {total} archived links are dead");
+        }
+        Some(Commands::Diff { url }) => match db.get_last_two_versions(url.clone()).await? {
+            Some((old, new)) => {
+                let diff = TextDiff::from_lines(&old, &new);
+                print!("{}", diff.unified_diff().header("previous", "latest"));
+            }
+            None => println!("no earlier crawl of {url} to compare with"),
+        },
+        Some(Commands::Activity { days }) => {
+            let activity = db.get_activity().await?;
+
+            let today = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64 / 86400;
+            let (current, longest) = activity::streaks(&activity, today);
+            let total: usize = activity.iter().map(|d| d.count).sum();
+
+            for day in activity.iter().rev().take(days).rev() {
+                println!(
+                    "{}  {:>3} {}",
+                    day.date,
+                    day.count,
+                    "#".repeat(day.count.min(50))
+                );
+            }
+
+            println!("{total} reads on {} days", activity.len());
+            println!("current streak: {current} days, longest streak: {longest} days");
+        }
+        Some(Commands::LabelClusters { remove: true, .. }) => {
+            let removed = db.remove_tags_with_prefix(clusters::TAG_PREFIX).await?;
+            println!("removed cluster tags from {removed} items");
+        }
+        Some(Commands::LabelClusters {
+            clusters, labels, ..
+        }) => {
+            let clusters: Clusters = match clusters {
+                Some(path) => serde_json::from_reader(File::open(path)?)?,
+                None => db.get_clusters().await?,
+            };
+            let labels: HashMap<String, String> = match labels {
+                Some(path) => serde_json::from_reader(File::open(path)?)?,
+                None => HashMap::new(),
+            };
+
+            let items = db.get_item_tags().await?;
+            let labelled = clusters::label_clusters(&clusters, &items, &labels);
+
+            let mut tags = vec![];
+            for (id, label) in &labelled {
+                println!("cluster {id}: {}{label}", clusters::TAG_PREFIX);
+                for url in &clusters[id] {
+                    tags.push((url.clone(), format!("{}{label}", clusters::TAG_PREFIX)));
                 }
+            }
 
-                // Finally, do mean pooling to determine the document embedding.
-                let embeddings = data.into_iter().map(|ed| ed.embedding).collect::<Vec<_>>();
-                let doc_vector = mean_pooling_ndarray(&embeddings)?.to_vec();
+            let tagged = db.tag_untagged(tags).await?;
+            println!("tagged {tagged} untagged items");
+        }
+        Some(Commands::Merge { other }) => {
+            let report = db.merge(other).await?;
+            println!(
+                "{} added, {} updated, {} skipped, {} chunks copied",
+                report.added, report.updated, report.skipped, report.chunks
+            );
+        }
+        Some(Commands::NormalizeVectors) => {
+            let normalized = db.normalize_doc_vectors().await?;
+            println!("Normalized {normalized} article embeddings");
+        }
+        Some(Commands::Dedup) => {
+            let merged = db.merge_duplicates().await?;
+            println!("Merged {merged} duplicate items");
+        }
+        Some(Commands::Vacuum) => {
+            let before = db_file_size(&db_path);
+            db.maintenance().await?;
+            let after = db_file_size(&db_path);
 
-                db.save_doc_vector(c.url, &doc_vector).await?;
+            println!(
+                "Database went from {} to {}",
+                HumanBytes(before),
+                HumanBytes(after)
+            );
+        }
+        Some(Commands::Search { query, n }) => {
+            for hit in db.search(query, n).await? {
+                println!(
+                    "{}\n  {}\n  {}\n",
+                    hit.title,
+                    hit.url,
+                    hit.snippet.replace('\n', " ")
+                );
             }
         }
-        Some(Commands::Cluster) => {
-            let items = db.get_urls_with_doc_vector().await?;
-            println!("{}", serde_json::to_string(&items)?);
+        Some(Commands::SemanticSearch { query, n, embedder }) => {
+            semantic_search(&db, query, n, embedder).await?;
         }
         None => {}
     }
@@ -211,55 +1302,616 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-#[derive(Debug, Serialize)]
-struct EmbeddingRequest<'a> {
-    model: String,
-    input: Vec<&'a str>,
+/// The size of the database at `path` on disk, counting its write-ahead log.
+fn db_file_size(path: &Path) -> u64 {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+
+    [path.as_os_str(), &wal]
+        .into_iter()
+        .filter_map(|file| fs::metadata(file).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Runs Readability over pages we already fetched and saves the results over the old ones.
+async fn extract_all(db: &Db, pages: Vec<RawPage>) -> Result<()> {
+    // Readability is CPU bound, so there's no point in running more at once than we have
+    // cores for.
+    let parallelism = thread::available_parallelism().map_or(4, |n| n.get());
+
+    let mut pages = pages.into_iter();
+    let mut extractions = JoinSet::new();
+    loop {
+        while extractions.len() < parallelism
+            && let Some(page) = pages.next()
+        {
+            extractions.spawn_blocking(move || worker::extract(page));
+        }
+
+        let Some(result) = extractions.join_next().await else {
+            break;
+        };
+
+        match result {
+            Ok(Ok(article)) => {
+                println!(
+                    "{} {} bytes of text, ~{} tokens",
+                    article.url,
+                    article.markdown.len(),
+                    estimate_tokens(&article.markdown)
+                );
+                db.save_crawl(article, OverwritePolicy::Always).await?;
+            }
+            Ok(Err(err)) => eprintln!("Extraction error: {err}"),
+            Err(_) => eprintln!("dom_smoothie parser panicked"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Saves imported items, telling the archive hook about each new one.
+async fn import_items(db: &Db, export: Export, args: &ImportArgs) -> Result<()> {
+    if !export.skipped.is_empty() {
+        println!("skipping {} rows we couldn't read:", export.skipped.len());
+        for (row, err) in &export.skipped {
+            println!("  row {row}: {err:#}");
+        }
+    }
+    let items = export.items;
+
+    let existing = if args.skip_existing || args.dry_run {
+        db.get_all_urls().await?
+    } else {
+        HashSet::new()
+    };
+
+    let (items, present): (Vec<_>, Vec<_>) = items
+        .into_iter()
+        .filter(|(row, _)| *row > args.resume_from)
+        .map(|(_, item)| item)
+        .partition(|item| !args.skip_existing || !existing.contains(item.url.as_str()));
+
+    if args.resume_from > 0 {
+        println!("resuming after {} rows", args.resume_from);
+    }
+    if args.skip_existing {
+        println!("skipping {} items we already have", present.len());
+    }
+
+    if args.dry_run {
+        let new = items
+            .iter()
+            .filter(|item| !existing.contains(item.url.as_str()))
+            .count();
+        let archived = items
+            .iter()
+            .filter(|item| matches!(item.status, PocketStatus::Archive))
+            .count();
+        let tagged = items.iter().filter(|item| !item.tags.is_empty()).count();
+
+        println!(
+            "would save {} items: {new} new, {} we already have",
+            items.len(),
+            items.len() - new
+        );
+        println!(
+            "{} unread, {archived} archived, {tagged} with tags",
+            items.len() - archived
+        );
+        for item in items.iter().take(DRY_RUN_SAMPLE) {
+            println!(
+                "  {} [{}] {} {}",
+                item.status, item.tags, item.title, item.url
+            );
+        }
+
+        return Ok(());
+    }
+
+    let hook = archive_hook()?;
+
+    for item in items {
+        let url = item.url.to_string();
+        let payload = match &hook {
+            Some(_) => Some(serde_json::to_value(&item)?),
+            None => None,
+        };
+
+        match db.save_item(item).await {
+            Ok(true) => {
+                if let Some(hook) = &hook
+                    && let Some(payload) = &payload
+                    && let Err(err) = hook.notify(payload).await
+                {
+                    eprintln!("archive hook failed for {url}: {err}");
+                }
+            }
+            Ok(false) => {}
+            Err(_) => return Err(anyhow!("failed to insert item for {url}...")),
+        }
+    }
+
+    Ok(())
+}
+
+/// The configured archive hook, with a client that gives up on a hanging endpoint rather than
+/// stalling the command that archived something.
+fn archive_hook() -> Result<Option<ArchiveHook>> {
+    let client = Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(Duration::from_secs(TIMEOUT_SECS))
+        .build()?;
+
+    ArchiveHook::from_env(client)
+}
+
+/// Embeds `items` with the backend and model `args` ask for.
+async fn embed_all(
+    db: &Db,
+    items: Vec<ItemForChunking>,
+    args: &EmbedArgs,
+    verbose: bool,
+) -> Result<()> {
+    let progress = Progress::new(items.len(), "embedded", verbose);
+    let model = args.embedder.model.clone();
+    match args.embedder.backend {
+        EmbedBackend::OpenRouter => {
+            let model = model.unwrap_or(embedding::DEFAULT_MODEL.to_string());
+            let embedder = OpenRouterEmbedder::from_env(Client::new(), model)?
+                .with_progress(progress.bar.clone());
+            embed_with(db, items, args, embedder, progress).await
+        }
+        #[cfg(feature = "local-embeddings")]
+        EmbedBackend::Local => {
+            let embedder = LocalEmbedder::new(model.as_deref().unwrap_or(DEFAULT_LOCAL_MODEL))?;
+            embed_with(db, items, args, embedder, progress).await
+        }
+        #[cfg(not(feature = "local-embeddings"))]
+        EmbedBackend::Local => Err(no_local_embeddings()),
+    }
+}
+
+#[cfg(not(feature = "local-embeddings"))]
+fn no_local_embeddings() -> anyhow::Error {
+    anyhow!("this build can't embed locally, build it with `--features local-embeddings`")
+}
+
+/// Searches the embedded articles for the ones closest in meaning to `query`, embedding it with
+/// the backend and model `args` ask for.
+async fn semantic_search(db: &Db, query: String, n: usize, args: EmbedderArgs) -> Result<()> {
+    match args.backend {
+        EmbedBackend::OpenRouter => {
+            let model = args.model.unwrap_or(embedding::DEFAULT_MODEL.to_string());
+            let embedder = OpenRouterEmbedder::from_env(Client::new(), model)?;
+            semantic_search_with(db, query, n, embedder).await
+        }
+        #[cfg(feature = "local-embeddings")]
+        EmbedBackend::Local => {
+            let embedder =
+                LocalEmbedder::new(args.model.as_deref().unwrap_or(DEFAULT_LOCAL_MODEL))?;
+            semantic_search_with(db, query, n, embedder).await
+        }
+        #[cfg(not(feature = "local-embeddings"))]
+        EmbedBackend::Local => Err(no_local_embeddings()),
+    }
+}
+
+async fn semantic_search_with(
+    db: &Db,
+    query: String,
+    n: usize,
+    embedder: impl Embedder,
+) -> Result<()> {
+    let model = embedder.model().to_string();
+    let (embedded, crawled) = db.count_embedded(model.clone()).await?;
+    if embedded == 0 {
+        return Err(anyhow!(
+            "no articles are embedded with {model} yet, run `embed` first"
+        ));
+    }
+
+    let query = embedder
+        .embed(&[&query])
+        .await?
+        .pop()
+        .ok_or_else(|| anyhow!("got no embedding for the query"))?;
+
+    for item in db.get_nearest(query, model.clone(), n).await? {
+        println!("{:.2}  {}\n      {}", item.similarity, item.title, item.url);
+    }
+
+    // Articles without an embedding can't turn up, however well they'd match.
+    if embedded < crawled {
+        println!(
+            "\nOnly searched the {embedded} of {crawled} crawled articles embedded with {model}, \
+            run `embed` to search the rest"
+        );
+    }
+
+    Ok(())
+}
+
+/// Embeds `items` chunk by chunk and saves both the chunk embeddings and the document embedding.
+async fn embed_with(
+    db: &Db,
+    items: Vec<ItemForChunking>,
+    args: &EmbedArgs,
+    embedder: impl Embedder,
+    progress: Progress,
+) -> Result<()> {
+    // Create our semantic chunker for markdown with a high max (by default) because
+    // we're using our embeddings for clustering and not for retrieval.
+    // That's why we can be less precise.
+    let chunker = Chunker::new(args.chunk_size, args.chunk_overlap, args.heading_context)?;
+
+    let mut too_large = vec![];
+    let mut failed = 0;
+
+    // Pace our requests so big runs don't trip the provider's rate limits.
+    let mut pacer = match args.embed_rps {
+        Some(rps) if rps > 0.0 => {
+            let mut interval = time::interval(Duration::from_secs_f64(1.0 / rps));
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            Some(interval)
+        }
+        Some(_) => return Err(anyhow!("--embed-rps must be positive")),
+        None => None,
+    };
+
+    // Keep up to `concurrency` articles in flight, saving each as it comes back.
+    let mut candidates = items.into_iter();
+    let mut requests = JoinSet::new();
+    loop {
+        while requests.len() < args.concurrency
+            && let Some(c) = candidates.next()
+        {
+            let chunks = chunker.chunks(&c.markdown);
+
+            // The API rejects these anyway, no need to fail the whole run over them.
+            let largest = chunks.iter().map(|c| estimate_tokens(c)).max();
+            if let Some(tokens) = largest.filter(|t| *t > args.max_tokens) {
+                progress.item(format!(
+                    "too large - {} (~{tokens} tokens in one chunk)",
+                    c.url
+                ));
+                progress.inc();
+                too_large.push((c.url, tokens));
+                continue;
+            }
+
+            if let Some(pacer) = &mut pacer {
+                pacer.tick().await;
+            }
+
+            let embedder = embedder.clone();
+            requests.spawn(async move {
+                let texts: Vec<&str> = chunks.iter().map(String::as_str).collect();
+                let embeddings = embedder.embed(&texts).await;
+                (c.url, chunks, embeddings)
+            });
+        }
+
+        let Some(joined) = requests.join_next().await else {
+            break;
+        };
+
+        progress.inc();
+        let (url, chunks, embeddings) = match joined {
+            Ok(embedded) => embedded,
+            Err(err) => {
+                progress.error(format!("embedding task failed: {err}"));
+                failed += 1;
+                continue;
+            }
+        };
+
+        // Pool the chunks' embeddings into the document embedding. This fails on e.g. an empty
+        // response, which is worth skipping the article over but not the whole run.
+        let pooled = embeddings.and_then(|embeddings| {
+            let doc_vector = args.pooling.pool(&embeddings)?;
+            Ok((embeddings, doc_vector))
+        });
+        let (embeddings, mut doc_vector) = match pooled {
+            Ok(pooled) => pooled,
+            Err(err) => {
+                progress.error(format!("failed to embed {url}: {err:#}"));
+                failed += 1;
+                continue;
+            }
+        };
+        progress.item(format!("embedded {url} ({} chunks)", chunks.len()));
+
+        if args.normalize {
+            doc_vector = pooling::normalize(&doc_vector);
+        }
+
+        db.save_embeddings(
+            url,
+            zip(chunks, embeddings).collect(),
+            &doc_vector.to_vec(),
+            embedder.model().to_string(),
+        )
+        .await?;
+    }
+
+    progress.finish();
+
+    if failed > 0 {
+        println!("Failed to embed {failed} articles, see above");
+    }
+
+    if !too_large.is_empty() {
+        println!(
+            "Skipped {} articles with chunks over {} tokens:",
+            too_large.len(),
+            args.max_tokens
+        );
+        for (url, tokens) in too_large {
+            println!("  ~{tokens} tokens - {url}");
+        }
+    }
+
+    Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-struct EmbeddingResponse {
-    id: String,
-    object: String,
-    data: Vec<EmbeddingData>,
-    model: String,
-    usage: Usage,
-    provider: String,
+/// Crawls `items` on a pool of workers and saves the results as they come in. Without `extract`,
+/// only the raw HTML is saved. Returns whether the crawl was interrupted with Ctrl-C.
+async fn crawl(
+    db: &Db,
+    items: Vec<ItemHandle>,
+    policy: OverwritePolicy,
+    fetch: &FetchArgs,
+    extract: bool,
+    verbose: bool,
+) -> Result<bool> {
+    let progress = Progress::new(items.len(), "crawled", verbose);
+
+    // Create channel for distributing work items.
+    let (work_q, r) = async_channel::bounded(fetch.queue_size);
+
+    // Create an HTTP client that can be shared (internal connection pool).
+    let client = fetch.client_builder()?.build()?;
+
+    // Shared by all workers, so each host's robots.txt is only fetched once.
+    let robots = Arc::new(Robots::new(client.clone()));
+    let limiter = Arc::new(HostLimiter::new(fetch.per_host_rps));
+    let retries = fetch.retries();
+
+    // Spawn a pool of worker tasks for crawling and cleaning.
+    let mut workers = JoinSet::new();
+    for _ in 0..fetch.workers {
+        let r_i = r.clone();
+        let c_i = client.clone();
+        let robots_i = robots.clone();
+        let limiter_i = limiter.clone();
+        workers.spawn(async move {
+            spawn_worker(c_i, r_i, robots_i, limiter_i, retries, extract).await
+        });
+    }
+
+    // Results channel for work output
+    let (results_tx, mut results_rx) = mpsc::channel(fetch.queue_size);
+
+    let worker_tx = results_tx.clone();
+
+    // Spawn a Seeder task so we can start consuming results while
+    // we're still pushing work on the queue.
+    tokio::spawn(async move {
+        for item in items {
+            let work = WorkItem {
+                url: item.url,
+                circle_back: worker_tx.clone(),
+            };
+            // The queue is closed when we're interrupted.
+            if work_q.send(work).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Prevent that we keep one sender open!
+    drop(results_tx);
+
+    let ctrl_c = signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut interrupted = false;
+    let (mut processed, mut failed) = (0, 0);
+    // Saving articles one transaction at a time is slow for big crawls, so we save them in
+    // batches. An interrupted crawl still ends the loop normally, so nothing buffered is lost.
+    let mut batch = Vec::with_capacity(CRAWL_BATCH);
+
+    loop {
+        let worker_output = tokio::select! {
+            output = results_rx.recv() => match output {
+                Some(output) => output,
+                None => break,
+            },
+            _ = &mut ctrl_c, if !interrupted => {
+                progress.error("Interrupted, waiting for the pages being crawled now...");
+                interrupted = true;
+                // Stop seeding and throw away what's still queued. The workers finish their
+                // current page and then find the queue closed, which closes the results channel
+                // once their results are in.
+                r.close();
+                while r.try_recv().is_ok() {}
+                continue;
+            }
+        };
+
+        processed += 1;
+        progress.inc();
+        match worker_output {
+            Ok(Crawled::Article(article)) => {
+                // Update our database with the extracted content
+                progress.item(format!(
+                    "{} - {} {} bytes of text, ~{} tokens",
+                    article.status,
+                    article.url,
+                    article.markdown.len(),
+                    estimate_tokens(&article.markdown)
+                ));
+                if article.final_url != article.url {
+                    progress.item(format!("  redirected to {}", article.final_url));
+                }
+                batch.push(article);
+                if batch.len() >= CRAWL_BATCH {
+                    save_crawls(db, std::mem::take(&mut batch), policy, &progress).await?;
+                }
+            }
+            Ok(Crawled::Disallowed(url)) => {
+                progress.item(format!("robots.txt disallows {url}, skipping it"));
+                db.save_skip_reason(url, "robots.txt").await?;
+            }
+            Ok(Crawled::Raw(page)) => {
+                progress.item(format!(
+                    "{} - {} {} bytes of HTML",
+                    page.status,
+                    page.url,
+                    page.html.len()
+                ));
+                let url = page.url.clone();
+                if !db.save_raw_crawl(page).await? {
+                    progress.item(format!("  not saved: {url} is unknown"));
+                }
+            }
+            Err(err) => {
+                failed += 1;
+                progress.error(format!("Worker error: {err}"));
+                db.save_crawl_error(err).await?;
+            }
+        }
+    }
+
+    save_crawls(db, batch, policy, &progress).await?;
+
+    // Wait for our full worker pool to finish cleaning up.
+    let report_cards = workers.join_all().await;
+    progress.finish();
+
+    println!(
+        "Processed {processed} items, {failed} of which failed{}",
+        if interrupted { " (interrupted)" } else { "" }
+    );
+    print_report_cards(&report_cards, verbose);
+
+    Ok(interrupted)
 }
 
-#[derive(Debug, Clone, Deserialize)]
-struct EmbeddingData {
-    object: String,
-    embedding: Vec<f32>,
-    index: usize,
+/// Prints how much the workers fetched and how busy they were, and with `verbose` how that was
+/// spread over them.
+fn print_report_cards(cards: &[ReportCard], verbose: bool) {
+    if verbose {
+        println!(
+            "{:>6} {:>6} {:>6} {:>10} {:>8}",
+            "worker", "items", "errors", "fetched", "busy"
+        );
+        for (i, card) in cards.iter().enumerate() {
+            println!(
+                "{:>6} {:>6} {:>6} {:>10} {:>7.1}s",
+                i + 1,
+                card.items,
+                card.errors,
+                HumanBytes(card.bytes).to_string(),
+                card.busy.as_secs_f32()
+            );
+        }
+    }
+
+    let bytes: u64 = cards.iter().map(|card| card.bytes).sum();
+    let busy: Duration = cards.iter().map(|card| card.busy).sum();
+    let least = cards.iter().map(|card| card.items).min().unwrap_or(0);
+    let most = cards.iter().map(|card| card.items).max().unwrap_or(0);
+
+    println!(
+        "Fetched {} with {} workers, busy {:.1}s in total, {least}-{most} items each",
+        HumanBytes(bytes),
+        cards.len(),
+        busy.as_secs_f32()
+    );
 }
 
-#[derive(Debug, Deserialize)]
-struct Usage {
-    prompt_tokens: usize,
-    total_tokens: usize,
-    cost: f32,
+/// Fails if `items` were embedded with different models, whose vectors can't be compared.
+fn check_single_model(items: &[UrlWithDocVector]) -> Result<()> {
+    let mut models = BTreeMap::new();
+    for item in items {
+        *models
+            .entry(item.model.as_deref().unwrap_or("unknown"))
+            .or_insert(0) += 1;
+    }
+
+    if models.len() > 1 {
+        let counts = models
+            .iter()
+            .map(|(model, n)| format!("{model} ({n})"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(anyhow!(
+            "articles were embedded with different models: {counts}, pick one with --model"
+        ));
+    }
+
+    Ok(())
 }
 
-fn mean_pooling_ndarray(embeddings: &[Vec<f32>]) -> Result<Array1<f32>> {
-    if embeddings.is_empty() {
-        return Err(anyhow!("No embeddings provided"));
+/// Saves a batch of crawled articles, noting the ones that weren't saved.
+async fn save_crawls(
+    db: &Db,
+    articles: Vec<CrawledArticle>,
+    policy: OverwritePolicy,
+    progress: &Progress,
+) -> Result<()> {
+    let urls: Vec<Url> = articles.iter().map(|a| a.url.clone()).collect();
+
+    for (url, saved) in urls.iter().zip(db.save_crawls(articles, policy).await?) {
+        if !saved {
+            progress.item(format!(
+                "  not saved: {url} is unknown or we have a better crawl of it"
+            ));
+        }
     }
 
-    let rows = embeddings.len();
-    let cols = embeddings[0].len();
+    Ok(())
+}
+
+/// Sends a `HEAD` request to each item's URL on a pool of workers, fetching the way `fetch` asks,
+/// and returns the results as they come in.
+fn head_check_all(
+    fetch: &FetchArgs,
+    items: Vec<ItemHandle>,
+) -> Result<mpsc::Receiver<HeadWorkerOutput>> {
+    let client = fetch.client_builder()?.build()?;
+    let robots = Arc::new(Robots::new(client.clone()));
+    let limiter = Arc::new(HostLimiter::new(fetch.per_host_rps));
+    let retries = fetch.retries();
 
-    // Flatten the Vec<Vec<f32>> into a single Vec to create an Array2
-    let flat_data: Vec<f32> = embeddings.iter().flatten().cloned().collect();
+    let (work_q, r) = async_channel::bounded(fetch.queue_size);
 
-    // Create a 2D Matrix (Rows = Chunks, Cols = Dimensions)
-    let matrix = Array2::from_shape_vec((rows, cols), flat_data)?;
+    for _ in 0..fetch.workers {
+        let r_i = r.clone();
+        let c_i = client.clone();
+        let robots_i = robots.clone();
+        let limiter_i = limiter.clone();
+        tokio::spawn(
+            async move { spawn_head_worker(c_i, r_i, robots_i, limiter_i, retries).await },
+        );
+    }
 
-    // Calculate mean along Axis 0 (collapsing rows down to one)
-    // This returns an Array1<f32>
-    let mean_vector = matrix
-        .mean_axis(Axis(0))
-        .ok_or(anyhow!("Calculation failed"))?;
+    let (results_tx, results_rx) = mpsc::channel(fetch.queue_size);
+
+    // The workers exit once the seeder is done and drops the queue, and the results channel closes
+    // once the last work item's sender is dropped.
+    tokio::spawn(async move {
+        for item in items {
+            let _ = work_q
+                .send(HeadWorkItem {
+                    url: item.url,
+                    circle_back: results_tx.clone(),
+                })
+                .await;
+        }
+    });
 
-    Ok(mean_vector)
+    Ok(results_rx)
 }
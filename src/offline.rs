@@ -0,0 +1,180 @@
+//! Static HTML export of crawled articles, for reading without a connection (or this tool).
+
+use std::fmt::Write;
+
+use chrono::DateTime;
+use sha2::{Digest, Sha256};
+
+use crate::opml::escape;
+
+/// A crawled article, with what we need to render it on its own page.
+#[derive(Debug)]
+pub struct OfflineArticle {
+    pub url: String,
+    pub title: String,
+    pub author: Option<String>,
+    pub published_at: Option<i64>,
+    pub time_added: i64,
+    /// The article's content as cleaned up by Readability.
+    pub html: String,
+}
+
+impl OfflineArticle {
+    /// The file name of the article's page: a readable bit of the URL, and a bit of its hash so
+    /// different URLs never end up in the same file.
+    pub fn file_name(&self) -> String {
+        let readable = self
+            .url
+            .split_once("://")
+            .map_or(self.url.as_str(), |(_, rest)| rest);
+        let slug: String = readable
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .take(SLUG_LEN)
+            .collect();
+        let slug = slug.trim_matches('-');
+
+        let hash: String = Sha256::digest(&self.url)
+            .iter()
+            .take(4)
+            .map(|b| format!("{b:02x}"))
+            .collect();
+
+        format!("{slug}-{hash}.html")
+    }
+}
+
+/// How much of the URL goes into an article's file name.
+const SLUG_LEN: usize = 60;
+
+/// Styles for every page, inlined so each file stands on its own.
+const STYLES: &str = "
+body { max-width: 42em; margin: 2em auto; padding: 0 1em; font: 18px/1.6 Georgia, serif;
+    color: #222; background: #fdfdf8; }
+h1 { line-height: 1.2; }
+a { color: #1a5a96; }
+img, video, figure { max-width: 100%; height: auto; }
+pre { overflow-x: auto; padding: 0.5em; background: #f0f0ea; }
+blockquote { margin-left: 0; padding-left: 1em; border-left: 3px solid #ccc; color: #555; }
+.meta { color: #777; font-size: 0.85em; }
+.index li { margin-bottom: 0.5em; }
+@media (prefers-color-scheme: dark) {
+    body { color: #ddd; background: #1c1c1a; }
+    a { color: #8ab4e0; }
+    pre { background: #2a2a27; }
+    blockquote { border-left-color: #555; color: #aaa; }
+    .meta { color: #999; }
+}
+";
+
+/// Renders an article as a standalone HTML page.
+pub fn render_article(article: &OfflineArticle) -> String {
+    let title = display_title(article);
+
+    let mut meta = vec![format!(
+        "<a href=\"{}\">{}</a>",
+        escape(&article.url),
+        escape(host(&article.url))
+    )];
+    if let Some(author) = &article.author {
+        meta.push(format!("by {}", escape(author)));
+    }
+    if let Some(published) = article.published_at {
+        meta.push(format!("published {}", date(published)));
+    }
+    meta.push(format!("saved {}", date(article.time_added)));
+
+    let body = format!(
+        "<p><a href=\"index.html\">&larr; All articles</a></p>\n\
+        <h1>{}</h1>\n<p class=\"meta\">{}</p>\n<article>\n{}\n</article>",
+        escape(title),
+        meta.join(" &middot; "),
+        article.html
+    );
+
+    page(title, &body)
+}
+
+/// Renders the page linking to all the articles.
+pub fn render_index(articles: &[OfflineArticle]) -> String {
+    let mut body = format!(
+        "<h1>Reading list</h1>\n<p class=\"meta\">{} articles</p>\n",
+        articles.len()
+    );
+    body.push_str("<ul class=\"index\">\n");
+    for article in articles {
+        let _ = writeln!(
+            body,
+            "<li><a href=\"{}\">{}</a> <span class=\"meta\">{}</span></li>",
+            escape(&article.file_name()),
+            escape(display_title(article)),
+            escape(host(&article.url))
+        );
+    }
+    body.push_str("</ul>");
+
+    page("Reading list", &body)
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+        <title>{}</title>\n<style>{STYLES}</style>\n</head>\n<body>\n{body}\n</body>\n</html>\n",
+        escape(title)
+    )
+}
+
+fn display_title(article: &OfflineArticle) -> &str {
+    if article.title.trim().is_empty() {
+        &article.url
+    } else {
+        &article.title
+    }
+}
+
+fn host(url: &str) -> &str {
+    url.split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or(url)
+}
+
+fn date(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|dt| dt.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn article_pages() {
+        let article = OfflineArticle {
+            url: "https://example.com/posts/one?page=2".to_string(),
+            title: "Cats & dogs".to_string(),
+            author: Some("Ada".to_string()),
+            published_at: None,
+            time_added: 1614076299,
+            html: "<p>Content</p>".to_string(),
+        };
+
+        let file_name = article.file_name();
+        let hash = file_name
+            .strip_prefix("example-com-posts-one-page-2-")
+            .and_then(|rest| rest.strip_suffix(".html"))
+            .unwrap();
+        assert_eq!(hash.len(), 8);
+
+        let page = render_article(&article);
+        assert!(page.contains("<title>Cats &amp; dogs</title>"));
+        assert!(page.contains("<p>Content</p>"));
+        assert!(page.contains("by Ada &middot; saved 2021-02-23"));
+
+        let index = render_index(&[article]);
+        assert!(index.contains("example-com-posts-one-page-2-"));
+    }
+}
@@ -0,0 +1,108 @@
+//! OPML export of the reading list, which most feed readers and bookmark tools can import.
+
+use std::fmt::Write;
+
+/// An item on the reading list, as we export it.
+#[derive(Debug)]
+pub struct ListItem {
+    pub url: String,
+    pub title: String,
+    pub tags: Vec<String>,
+    /// When the item was saved, as a Unix timestamp.
+    pub time_added: i64,
+}
+
+/// Renders `items` as an OPML 2.0 document with one link outline per item.
+pub fn to_opml(title: &str, items: &[ListItem]) -> String {
+    let mut opml = String::new();
+
+    opml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    opml.push_str("<opml version=\"2.0\">\n");
+    let _ = writeln!(
+        opml,
+        "  <head>\n    <title>{}</title>\n  </head>",
+        escape(title)
+    );
+    opml.push_str("  <body>\n");
+
+    for item in items {
+        // Pocket doesn't always have a title, and readers show `text` as the outline's name.
+        let text = if item.title.trim().is_empty() {
+            &item.url
+        } else {
+            &item.title
+        };
+        let _ = write!(
+            opml,
+            "    <outline type=\"link\" text=\"{}\" url=\"{}\" htmlUrl=\"{}\" created=\"{}\"",
+            escape(text),
+            escape(&item.url),
+            escape(&item.url),
+            rfc822(item.time_added),
+        );
+        if !item.tags.is_empty() {
+            let _ = write!(opml, " category=\"{}\"", escape(&item.tags.join(",")));
+        }
+        opml.push_str("/>\n");
+    }
+
+    opml.push_str("  </body>\n</opml>\n");
+    opml
+}
+
+/// OPML wants its dates the way RSS has them.
+fn rfc822(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|dt| dt.to_rfc2822())
+        .unwrap_or_default()
+}
+
+/// Escapes text for use in an XML or HTML attribute or element.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Not allowed in XML 1.0 at all, and only ever junk in a title.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outlines() {
+        let items = [
+            ListItem {
+                url: "https://example.com/?a=1&b=2".to_string(),
+                title: "Tom & Jerry <3".to_string(),
+                tags: vec!["fun".to_string(), "cats".to_string()],
+                time_added: 1614076299,
+            },
+            ListItem {
+                url: "https://example.com/untitled".to_string(),
+                title: String::new(),
+                tags: vec![],
+                time_added: 1614076299,
+            },
+        ];
+
+        let opml = to_opml("Reading list", &items);
+
+        assert!(opml.contains(
+            r#"<outline type="link" text="Tom &amp; Jerry &lt;3" url="https://example.com/?a=1&amp;b=2" htmlUrl="https://example.com/?a=1&amp;b=2" created="Tue, 23 Feb 2021 10:31:39 +0000" category="fun,cats"/>"#
+        ));
+        assert!(opml.contains(r#"text="https://example.com/untitled""#));
+    }
+}
@@ -1,10 +1,15 @@
 //! Helper utilities to read Pocket exports for seeding our crawler.
 
-use std::{fmt, io::Read};
+use std::{
+    fmt, fs,
+    io::{Cursor, Read},
+    path::Path,
+};
 
-use anyhow::Error;
+use anyhow::{Error, anyhow};
 use reqwest::Url;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
 
 /// Reader for Pocket CSV export files.
 pub struct PocketReader<R> {
@@ -20,20 +25,111 @@ where
         Self { reader }
     }
 
-    /// Processes all CSV rows into [`PocketItem`]s.
-    pub fn read(self) -> Result<Vec<PocketItem>, Error> {
+    /// Processes all CSV rows into [`PocketItem`]s, skipping the rows we can't make sense of.
+    pub fn read(self) -> Result<Export, Error> {
         let mut reader = csv::Reader::from_reader(self.reader);
-        let mut items: Vec<PocketItem> = vec![];
+        let mut export = Export::default();
 
-        for item in reader.deserialize() {
-            items.push(item?);
+        for (i, item) in reader.deserialize().enumerate() {
+            export.push(i + 1, item.map_err(Error::from));
         }
 
-        Ok(items)
+        Ok(export)
+    }
+
+    /// Processes a JSON array of items, with the same fields as the CSV, into [`PocketItem`]s.
+    pub fn read_json(self) -> Result<Export, Error> {
+        let values: Vec<serde_json::Value> = serde_json::from_reader(self.reader)?;
+        let mut export = Export::default();
+
+        for (i, value) in values.into_iter().enumerate() {
+            export.push(i + 1, serde_json::from_value(value).map_err(Error::from));
+        }
+
+        Ok(export)
+    }
+}
+
+/// What we got out of an export: the items, and the rows we couldn't read with why.
+#[derive(Debug, Default)]
+pub struct Export {
+    /// The items with the rows they came from, counting from 1 after the header.
+    pub items: Vec<(usize, PocketItem)>,
+    /// Row numbers of the rows we couldn't read, and what was wrong with them.
+    pub skipped: Vec<(usize, Error)>,
+    /// How many rows we've read, so rows keep counting on in the next part of an export.
+    rows: usize,
+}
+
+impl Export {
+    /// Adds the result of reading row `row`.
+    pub fn push(&mut self, row: usize, item: Result<PocketItem, Error>) {
+        self.rows = self.rows.max(row);
+        match item {
+            Ok(item) => self.items.push((row, item)),
+            Err(err) => self.skipped.push((row, err)),
+        }
+    }
+
+    /// Adds everything from another part of a multi-file export, numbering its rows after ours.
+    pub fn extend(&mut self, part: Export, name: &str) {
+        let offset = self.rows;
+        self.items.extend(
+            part.items
+                .into_iter()
+                .map(|(row, item)| (offset + row, item)),
+        );
+        self.skipped.extend(
+            part.skipped
+                .into_iter()
+                .map(|(row, err)| (offset + row, err.context(format!("in {name}")))),
+        );
+        self.rows += part.rows;
     }
 }
 
-#[derive(Debug, Deserialize)]
+impl PocketReader<fs::File> {
+    /// Reads the export at `path`, whether it's a CSV, the ZIP of CSVs Pocket hands out now, or a
+    /// JSON array. We go by the first bytes of the file rather than its extension, as downloads
+    /// tend to get renamed.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Export, Error> {
+        let bytes = fs::read(path)?;
+
+        if bytes.starts_with(b"PK\x03\x04") {
+            read_zip(&bytes)
+        } else if bytes.trim_ascii_start().starts_with(b"[") {
+            PocketReader::new(bytes.as_slice()).read_json()
+        } else {
+            PocketReader::new(bytes.as_slice()).read()
+        }
+    }
+}
+
+/// Reads every CSV in a ZIP export, as big exports are split over several parts.
+fn read_zip(bytes: &[u8]) -> Result<Export, Error> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+    let mut export = Export::default();
+    let mut found_csv = false;
+
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let name = file.name()?.into_owned();
+        if !name.to_lowercase().ends_with(".csv") {
+            continue;
+        }
+
+        found_csv = true;
+        export.extend(PocketReader::new(file).read()?, &name);
+    }
+
+    if !found_csv {
+        return Err(anyhow!("no CSV files in the ZIP export"));
+    }
+
+    Ok(export)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PocketItem {
     pub title: String,
     pub url: Url,
@@ -42,7 +138,7 @@ pub struct PocketItem {
     pub status: PocketStatus,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct PocketTags(Vec<Tag>);
 
 impl<'de> Deserialize<'de> for PocketTags {
@@ -50,13 +146,19 @@ impl<'de> Deserialize<'de> for PocketTags {
     where
         D: serde::Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-
-        if s.is_empty() {
-            return Ok(PocketTags(vec![]));
+        // The CSV joins tags with pipes, JSON may just list them.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawTags {
+            Joined(String),
+            List(Vec<String>),
         }
 
-        let tags = s.split('|').map(|tag| Tag(tag.to_string())).collect();
+        let tags = match RawTags::deserialize(deserializer)? {
+            RawTags::Joined(s) if s.is_empty() => vec![],
+            RawTags::Joined(s) => s.split('|').map(|tag| Tag(tag.to_string())).collect(),
+            RawTags::List(tags) => tags.into_iter().map(Tag).collect(),
+        };
 
         Ok(PocketTags(tags))
     }
@@ -86,15 +188,21 @@ impl IntoIterator for PocketTags {
 }
 
 impl PocketTags {
-    fn is_empty(&self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 }
 
-#[derive(Debug, Deserialize)]
+impl FromIterator<Tag> for PocketTags {
+    fn from_iter<I: IntoIterator<Item = Tag>>(iter: I) -> Self {
+        PocketTags(iter.into_iter().collect())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Tag(pub String);
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum PocketStatus {
     #[serde(rename = "unread")]
     Unread,
@@ -115,6 +223,10 @@ impl fmt::Display for PocketStatus {
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
     use super::*;
 
     #[test]
@@ -126,7 +238,72 @@ Taoism,https://en.wikipedia.org/wiki/Taoism,1614076299,meaning,unread
 https://www.yudkowsky.net/rational/virtues,https://www.yudkowsky.net/rational/virtues,1642196007,rationality|self improvement,unread";
 
         let pr = PocketReader::new(data.as_bytes());
-        let items = pr.read().expect("should parse pocket items correctly");
-        println!("{items:#?}");
+        let export = pr.read().expect("should parse pocket items correctly");
+        println!("{:#?}", export.items);
+        assert!(export.skipped.is_empty());
+    }
+
+    #[test]
+    fn skips_malformed_rows() {
+        let data = "\
+title,url,time_added,tags,status
+Taoism,https://en.wikipedia.org/wiki/Taoism,1614076299,meaning,unread
+Broken,not a url,1614076300,,unread
+Zen,https://en.wikipedia.org/wiki/Zen,1614076301,,archive
+Short row,https://example.com/short
+Tao Te Ching,https://en.wikipedia.org/wiki/Tao_Te_Ching,1614076302,,read";
+
+        let export = PocketReader::new(data.as_bytes()).read().unwrap();
+
+        let titles: Vec<_> = export
+            .items
+            .iter()
+            .map(|(_, item)| item.title.as_str())
+            .collect();
+        assert_eq!(titles, ["Taoism", "Zen"]);
+        let rows: Vec<_> = export.items.iter().map(|(row, _)| *row).collect();
+        assert_eq!(rows, [1, 3]);
+        let rows: Vec<_> = export.skipped.iter().map(|(row, _)| *row).collect();
+        assert_eq!(rows, [2, 4, 5]);
+    }
+
+    #[test]
+    fn zip_and_json_exports() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        for (name, contents) in [
+            (
+                "part_000000.csv",
+                "title,url,time_added,tags,status\nTaoism,https://en.wikipedia.org/wiki/Taoism,1614076299,meaning,unread\n",
+            ),
+            ("README.txt", "not an export"),
+            (
+                "part_000001.csv",
+                "title,url,time_added,tags,status\nZen,https://en.wikipedia.org/wiki/Zen,1614076300,,archive\n",
+            ),
+        ] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        let zip_path = dir.join("export.zip");
+        fs::write(&zip_path, zip.finish().unwrap().into_inner()).unwrap();
+
+        let items = PocketReader::from_path(&zip_path).unwrap().items;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].0, 2);
+        assert_eq!(items[1].1.title, "Zen");
+
+        let json_path = dir.join("export.json");
+        fs::write(
+            &json_path,
+            r#"[{"title": "Taoism", "url": "https://en.wikipedia.org/wiki/Taoism",
+                "time_added": 1614076299, "tags": ["meaning", "religion"], "status": "unread"}]"#,
+        )
+        .unwrap();
+
+        let items = PocketReader::from_path(&json_path).unwrap().items;
+        assert_eq!(items[0].1.tags.to_string(), "meaning,religion");
     }
 }
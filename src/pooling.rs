@@ -0,0 +1,116 @@
+//! Pooling an article's chunk embeddings into one document embedding.
+
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use ndarray::{Array1, Array2, Axis};
+
+/// How the chunk embeddings of an article become its document embedding.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Pooling {
+    /// the average of all chunks, so every part of the article counts; clusters articles by what
+    /// they're about overall
+    Mean,
+    /// the element-wise maximum over chunks, so whatever is strong in any one chunk shows; keeps
+    /// long articles that touch on several topics from averaging out into none of them
+    Max,
+    /// the first chunk only, which tends to say what the article is about; keeps long tails of
+    /// comments and footers from drowning out the introduction
+    Cls,
+}
+
+impl Pooling {
+    /// Pools `embeddings`, one per chunk and all of the same length, into one vector.
+    pub fn pool(self, embeddings: &[Vec<f32>]) -> Result<Array1<f32>> {
+        match self {
+            Pooling::Mean => mean_pooling(embeddings),
+            Pooling::Max => max_pooling(embeddings),
+            Pooling::Cls => embeddings
+                .first()
+                .map(|first| Array1::from_vec(first.clone()))
+                .ok_or(anyhow!("No embeddings provided")),
+        }
+    }
+}
+
+/// Stacks chunk embeddings into a matrix, one row per chunk.
+fn to_matrix(embeddings: &[Vec<f32>]) -> Result<Array2<f32>> {
+    if embeddings.is_empty() {
+        return Err(anyhow!("No embeddings provided"));
+    }
+
+    let rows = embeddings.len();
+    let cols = embeddings[0].len();
+
+    // Flatten the Vec<Vec<f32>> into a single Vec to create an Array2
+    let flat_data: Vec<f32> = embeddings.iter().flatten().cloned().collect();
+
+    // Create a 2D Matrix (Rows = Chunks, Cols = Dimensions)
+    Ok(Array2::from_shape_vec((rows, cols), flat_data)?)
+}
+
+/// Averages chunk embeddings into one document embedding.
+pub fn mean_pooling(embeddings: &[Vec<f32>]) -> Result<Array1<f32>> {
+    let matrix = to_matrix(embeddings)?;
+
+    // Calculate mean along Axis 0 (collapsing rows down to one)
+    // This returns an Array1<f32>
+    let mean_vector = matrix
+        .mean_axis(Axis(0))
+        .ok_or(anyhow!("Calculation failed"))?;
+
+    Ok(mean_vector)
+}
+
+/// Takes the element-wise maximum of chunk embeddings as the document embedding.
+pub fn max_pooling(embeddings: &[Vec<f32>]) -> Result<Array1<f32>> {
+    let matrix = to_matrix(embeddings)?;
+
+    Ok(matrix.fold_axis(Axis(0), f32::NEG_INFINITY, |max, x| max.max(*x)))
+}
+
+/// Scales `vector` to unit length, so cosine similarity is just the dot product. A vector of all
+/// zeros stays that way.
+pub fn normalize(vector: &Array1<f32>) -> Array1<f32> {
+    let norm = vector.dot(vector).sqrt();
+
+    if norm == 0.0 {
+        vector.clone()
+    } else {
+        vector / norm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies() {
+        let embeddings = vec![vec![1.0, -2.0, 0.5], vec![3.0, -4.0, 0.0]];
+
+        assert_eq!(
+            Pooling::Mean.pool(&embeddings).unwrap().to_vec(),
+            [2.0, -3.0, 0.25]
+        );
+        assert_eq!(
+            Pooling::Max.pool(&embeddings).unwrap().to_vec(),
+            [3.0, -2.0, 0.5]
+        );
+        assert_eq!(
+            Pooling::Cls.pool(&embeddings).unwrap().to_vec(),
+            [1.0, -2.0, 0.5]
+        );
+
+        assert!(Pooling::Max.pool(&[]).is_err());
+        assert!(Pooling::Cls.pool(&[]).is_err());
+    }
+
+    #[test]
+    fn unit_length() {
+        let vector = normalize(&Array1::from_vec(vec![3.0, -4.0]));
+        assert_eq!(vector.to_vec(), [0.6, -0.8]);
+        assert_eq!(normalize(&vector), vector);
+
+        assert_eq!(normalize(&Array1::zeros(2)).to_vec(), [0.0, 0.0]);
+    }
+}
@@ -0,0 +1,128 @@
+//! robots.txt handling for the crawler.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use reqwest::{Client, Url};
+use texting_robots::{Robot, get_robots_url};
+use tokio::{
+    sync::{Mutex as AsyncMutex, OnceCell},
+    time::{self, Instant},
+};
+
+/// The name we go by in robots.txt `User-agent` lines.
+const AGENT: &str = env!("CARGO_PKG_NAME");
+
+/// The robots.txt rules of every host we've crawled this run, fetched once per host.
+pub struct Robots {
+    client: Client,
+    hosts: Mutex<HashMap<String, Arc<OnceCell<HostRules>>>>,
+}
+
+impl Robots {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether robots.txt lets us fetch `url`. If the host asks for a crawl delay, this waits
+    /// until it's our turn before saying yes.
+    pub async fn allowed(&self, url: &Url) -> bool {
+        let Ok(robots_url) = get_robots_url(url.as_str()) else {
+            return true;
+        };
+
+        let cell = self
+            .hosts
+            .lock()
+            .expect("robots cache lock poisoned")
+            .entry(robots_url.clone())
+            .or_default()
+            .clone();
+
+        let rules = cell
+            .get_or_init(|| async {
+                match self.fetch(&robots_url).await {
+                    Some(txt) => HostRules::from_robots_txt(&txt),
+                    None => HostRules::default(),
+                }
+            })
+            .await;
+
+        if !rules.allows(url) {
+            return false;
+        }
+
+        if let Some(delay) = rules.delay {
+            let mut last = rules.last_request.lock().await;
+            if let Some(last) = *last {
+                time::sleep_until(last + delay).await;
+            }
+            *last = Some(Instant::now());
+        }
+
+        true
+    }
+
+    /// Fetches a robots.txt. Missing or unreachable ones put no restrictions on us.
+    async fn fetch(&self, robots_url: &str) -> Option<Vec<u8>> {
+        let res = self.client.get(robots_url).send().await.ok()?;
+        if !res.status().is_success() {
+            return None;
+        }
+
+        res.bytes().await.ok().map(|b| b.to_vec())
+    }
+}
+
+/// What one host's robots.txt allows us to do.
+#[derive(Default)]
+struct HostRules {
+    /// `None` if the host has no usable robots.txt, which means everything is allowed.
+    robot: Option<Robot>,
+    /// How long to wait between requests, from `Crawl-delay`.
+    delay: Option<Duration>,
+    last_request: AsyncMutex<Option<Instant>>,
+}
+
+impl HostRules {
+    fn from_robots_txt(txt: &[u8]) -> Self {
+        let robot = Robot::new(AGENT, txt).ok();
+        let delay = robot
+            .as_ref()
+            .and_then(|r| r.delay)
+            .and_then(|d| Duration::try_from_secs_f32(d).ok());
+
+        Self {
+            robot,
+            delay,
+            last_request: AsyncMutex::new(None),
+        }
+    }
+
+    fn allows(&self, url: &Url) -> bool {
+        self.robot.as_ref().is_none_or(|r| r.allowed(url.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disallowed_paths() {
+        let rules = HostRules::from_robots_txt(
+            b"User-agent: *\nDisallow: /private/\nCrawl-delay: 2\n\nUser-agent: other-bot\nDisallow: /\n",
+        );
+
+        let url = |path| Url::parse(&format!("https://example.com{path}")).unwrap();
+        assert!(rules.allows(&url("/posts/one")));
+        assert!(!rules.allows(&url("/private/notes")));
+        assert_eq!(rules.delay, Some(Duration::from_secs(2)));
+    }
+}
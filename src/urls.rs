@@ -0,0 +1,79 @@
+//! URL normalization for spotting the same article saved under different URLs.
+
+use reqwest::Url;
+
+/// Query parameters that only tell the site where a visitor came from.
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "igshid", "mc_cid", "mc_eid"];
+
+/// Reduces `url` to a form that is the same for all the ways the same page tends to get saved:
+/// `http` or `https`, with or without a trailing slash, fragment or tracking parameters. The host
+/// is already lowercased by [`Url`]. The path and the other parameters are left alone, as they
+/// can point to different pages.
+pub fn canonicalize(url: &Url) -> String {
+    let mut url = url.clone();
+
+    if url.scheme() == "http" {
+        // Only fails for URLs that can't have a scheme of http(s) anyway.
+        let _ = url.set_scheme("https");
+    }
+    url.set_fragment(None);
+
+    let query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !is_tracking_param(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if query.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query);
+    }
+
+    let path = url.path().to_string();
+    if path.len() > 1 && path.ends_with('/') {
+        url.set_path(path.trim_end_matches('/'));
+    }
+
+    url.to_string()
+}
+
+fn is_tracking_param(key: &str) -> bool {
+    key.starts_with("utm_") || TRACKING_PARAMS.contains(&key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(url: &str) -> String {
+        canonicalize(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn variants_of_the_same_page() {
+        let expected = "https://example.com/posts/one?page=2";
+
+        for url in [
+            "https://example.com/posts/one?page=2",
+            "http://example.com/posts/one?page=2",
+            "https://EXAMPLE.com/posts/one/?page=2",
+            "https://example.com/posts/one?utm_source=feed&page=2&utm_medium=rss",
+            "https://example.com/posts/one?page=2&fbclid=abc#comments",
+        ] {
+            assert_eq!(canonical(url), expected, "{url}");
+        }
+    }
+
+    #[test]
+    fn different_pages() {
+        assert_ne!(
+            canonical("https://example.com/posts/one"),
+            canonical("https://example.com/Posts/one")
+        );
+        assert_ne!(
+            canonical("https://example.com/posts?id=1"),
+            canonical("https://example.com/posts?id=2")
+        );
+        assert_eq!(canonical("https://example.com/"), "https://example.com/");
+    }
+}
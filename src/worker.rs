@@ -1,95 +1,804 @@
 //! Web crawler and parser.
 
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt, io,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{Result, anyhow};
 use async_channel::Receiver;
+use chrono::{DateTime, NaiveDate, NaiveTime};
+use dom_query::Document;
 use dom_smoothie::{Config, Readability, TextMode};
-use reqwest::{Client, StatusCode, Url};
-use tokio::sync::mpsc;
+use encoding_rs::{Encoding, UTF_8};
+use reqwest::{
+    Client, Method, Response, StatusCode, Url,
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+};
+use tokio::{sync::mpsc, task::JoinHandle, time};
+
+use crate::{limiter::HostLimiter, robots::Robots};
 
 pub type WorkerInbox = Receiver<WorkItem>;
-pub type WorkerOutput = Result<CrawledArticle>;
+pub type WorkerOutput = Result<Crawled, CrawlError>;
+pub type HeadWorkerInbox = Receiver<HeadWorkItem>;
+pub type HeadWorkerOutput = Result<HeadCheck, HeadError>;
 
 pub struct WorkItem {
     pub url: Url,
     pub circle_back: mpsc::Sender<WorkerOutput>,
 }
 
+pub struct HeadWorkItem {
+    pub url: Url,
+    pub circle_back: mpsc::Sender<HeadWorkerOutput>,
+}
+
+/// How many times to retry a fetch that failed for reasons that might go away on their own.
+#[derive(Debug, Clone, Copy)]
+pub struct Retries {
+    /// Retries for DNS failures, refused or reset connections and timeouts.
+    pub network: u32,
+    /// Retries for 5xx and 429 responses.
+    pub server: u32,
+    /// How long to wait before the first retry. Every retry after that waits twice as long, up
+    /// to [`MAX_BACKOFF`].
+    pub base_delay: Duration,
+}
+
+/// What a worker made of a page: the extracted article, or just the page when not extracting.
+#[derive(Debug)]
+pub enum Crawled {
+    Article(CrawledArticle),
+    Raw(RawPage),
+    /// The site's robots.txt doesn't want us to fetch this URL.
+    Disallowed(Url),
+}
+
+/// A fetched page before Readability has had a go at it.
+#[derive(Debug)]
+pub struct RawPage {
+    pub timestamp: u64,
+    pub status: StatusCode,
+    pub url: Url,
+    /// Where we ended up after following redirects.
+    pub final_url: Url,
+    pub html: String,
+}
+
 #[derive(Debug)]
 pub struct CrawledArticle {
     pub timestamp: u64,
     pub status: StatusCode,
     pub url: Url,
+    /// Where we ended up after following redirects.
+    pub final_url: Url,
+    /// The title Readability found, which tends to be better than Pocket's.
+    pub title: String,
+    /// Who wrote the article, if the page says.
+    pub author: Option<String>,
+    /// When the article was published, as a Unix timestamp.
+    pub published: Option<i64>,
+    /// The page as we fetched it, so we can extract it again without fetching it again.
+    pub raw_html: String,
+    /// The article's content as cleaned up by Readability.
     pub html: String,
     pub markdown: String,
+    /// The article as plain text, for tools that would trip over markdown. Embedding strips the
+    /// markdown itself, chunk by chunk, to keep going by its headings.
+    pub plain_text: String,
+    /// Links in the article to other pages.
+    pub links: Vec<Link>,
+    /// Whether we got a paywall or login wall instead of the article.
+    pub paywalled: bool,
+}
+
+#[derive(Debug)]
+pub struct Link {
+    pub href: String,
+    /// The link's anchor text.
+    pub text: String,
+}
+
+/// What a `HEAD` request tells us about an item without downloading it.
+#[derive(Debug)]
+pub struct HeadCheck {
+    pub status: StatusCode,
+    pub url: Url,
+    pub content_type: Option<String>,
+    pub content_length: Option<u64>,
+}
+
+/// A `HEAD` request that didn't get any response at all.
+#[derive(Debug)]
+pub struct HeadError {
+    pub url: Url,
+    pub cause: HeadFailure,
+}
+
+#[derive(Debug)]
+pub enum HeadFailure {
+    /// The site's robots.txt doesn't want us to fetch this URL, so we didn't.
+    Disallowed,
+    /// No response, even after retrying.
+    Failed(String),
+}
+
+impl fmt::Display for HeadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.cause {
+            HeadFailure::Disallowed => write!(f, "robots.txt disallows {}", self.url),
+            HeadFailure::Failed(reason) => write!(f, "{}: {reason}", self.url),
+        }
+    }
+}
+
+impl std::error::Error for HeadError {}
+
+/// Why crawling an item didn't give us a page.
+#[derive(Debug)]
+pub struct CrawlError {
+    pub url: Url,
+    pub message: String,
+}
+
+impl CrawlError {
+    fn new(url: Url, message: impl Into<String>) -> Self {
+        Self {
+            url,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for CrawlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.url, self.message)
+    }
+}
+
+impl std::error::Error for CrawlError {}
+
+/// Collects the links in an article's content to other pages, resolved against the article's URL.
+fn outbound_links(content: &Document, base: &Url) -> Vec<Link> {
+    let mut seen = HashSet::new();
+
+    content
+        .select("a[href]")
+        .iter()
+        .filter_map(|a| {
+            let mut href = base.join(&a.attr("href")?).ok()?;
+            href.set_fragment(None);
+
+            let outbound = matches!(href.scheme(), "http" | "https")
+                && href.as_str() != base.as_str().split('#').next()?;
+            if !outbound || !seen.insert(href.to_string()) {
+                return None;
+            }
+
+            let text = a.text().split_whitespace().collect::<Vec<_>>().join(" ");
+
+            Some(Link {
+                href: href.to_string(),
+                text,
+            })
+        })
+        .collect()
+}
+
+/// Decodes an HTML page in the charset its `Content-Type` header or `<meta>` tags say it's in.
+/// A byte order mark beats both, and pages that don't say anything are taken to be UTF-8.
+/// Anything that doesn't decode cleanly ends up as replacement characters.
+fn decode_html(body: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(charset)
+        .or_else(|| {
+            // Browsers only look this far into the document for a `<meta>` charset.
+            let head = String::from_utf8_lossy(&body[..body.len().min(1024)]);
+            charset(&head)
+        })
+        .unwrap_or(UTF_8);
+
+    let (html, _, _) = encoding.decode(body);
+
+    html.into_owned()
+}
+
+/// Finds the encoding named by the first `charset=` in `text`, which may be a `Content-Type`
+/// value or the start of an HTML document with `<meta charset>` or `<meta http-equiv>`.
+fn charset(text: &str) -> Option<&'static Encoding> {
+    let start = text.to_ascii_lowercase().find("charset=")? + "charset=".len();
+    let label = text[start..]
+        .trim_start_matches(['"', '\''])
+        .split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')))
+        .next()?;
+
+    Encoding::for_label(label.as_bytes())
+}
+
+/// Whether a `Content-Type` header value is HTML or one of its variants like XHTML, ignoring
+/// parameters such as the charset.
+pub fn is_html_content_type(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+
+    mime.eq_ignore_ascii_case("text/html") || mime.eq_ignore_ascii_case("application/xhtml+xml")
+}
+
+/// Parses the kinds of dates pages put in their metadata into a Unix timestamp.
+fn parse_date(date: &str) -> Option<i64> {
+    let date = date.trim();
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(date) {
+        return Some(dt.timestamp());
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc2822(date) {
+        return Some(dt.timestamp());
+    }
+
+    // Plain dates, or a date with a time but no offset. The day is all we care about anyway.
+    NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d")
+        .ok()
+        .map(|d| d.and_time(NaiveTime::MIN).and_utc().timestamp())
+}
+
+/// Finds the date of the first `<time datetime="...">` element, which blogs often use for the
+/// publication date when they have no metadata for it.
+fn first_time_element(html: &str) -> Option<i64> {
+    Document::from(html)
+        .select("time[datetime]")
+        .iter()
+        .find_map(|time| parse_date(&time.attr("datetime")?))
+}
+
+/// Pages behind a wall rarely get past a teaser, so longer articles don't count as walled even if
+/// they mention subscribing.
+const WALL_MAX_CHARS: usize = 1500;
+
+/// Phrases that give a paywall or login wall away, in lowercase.
+const WALL_PHRASES: &[&str] = &[
+    "subscribe to continue",
+    "subscribe to read",
+    "subscribers only",
+    "already a subscriber",
+    "sign in to continue",
+    "sign in to read",
+    "log in to continue",
+    "log in to read",
+    "create a free account",
+    "register to continue",
+    "to continue reading",
+];
+
+/// Guesses whether `markdown` is a paywall or login wall rather than the article: a short text
+/// that asks us to subscribe or sign in.
+fn looks_walled(markdown: &str) -> bool {
+    if markdown.chars().count() > WALL_MAX_CHARS {
+        return false;
+    }
+
+    let text = markdown.to_lowercase();
+    WALL_PHRASES.iter().any(|phrase| text.contains(phrase))
 }
 
-pub async fn spawn_worker(client: Client, inbox: WorkerInbox) {
-    // Readability config
+/// Runs Readability over a fetched page, turning it into an article. This is CPU heavy, so call
+/// it from a blocking thread.
+pub fn extract(page: RawPage) -> Result<CrawledArticle> {
     let cfg = Config {
         text_mode: TextMode::Markdown,
         ..Default::default()
     };
 
-    while let Ok(work) = inbox.recv().await {
-        // Fetch the website's content.
-        let Ok(res) = client.get(work.url.clone()).send().await else {
+    // Readability only takes URLs with a `://`, which e.g. `data:` URLs don't have.
+    let article = Readability::new(page.html.as_str(), Some(page.final_url.as_str()), Some(cfg))
+        .map_err(|e| anyhow!("can't extract {} from {}: {e}", page.url, page.final_url))?
+        .parse()
+        .map_err(|e| anyhow!("failed to parse {}: {e:?}", page.url))?;
+    let content = Document::from(article.content.clone());
+
+    Ok(CrawledArticle {
+        timestamp: page.timestamp,
+        status: page.status,
+        title: article.title.trim().to_string(),
+        author: article
+            .byline
+            .as_deref()
+            .map(str::trim)
+            .filter(|byline| !byline.is_empty())
+            .map(str::to_string),
+        published: article
+            .published_time
+            .as_deref()
+            .and_then(parse_date)
+            .or_else(|| first_time_element(&page.html)),
+        plain_text: content.formatted_text().to_string(),
+        links: outbound_links(&content, &page.final_url),
+        html: article.content.to_string(),
+        paywalled: looks_walled(&article.text_content),
+        markdown: article.text_content.to_string(),
+        raw_html: page.html,
+        url: page.url,
+        final_url: page.final_url,
+    })
+}
+
+/// Requests `url` with `method`, retrying network failures, server errors and rate limiting.
+/// Anything else, like a 404, won't get better by asking again.
+async fn fetch(
+    client: &Client,
+    method: Method,
+    url: &Url,
+    limiter: &HostLimiter,
+    retries: Retries,
+) -> reqwest::Result<Response> {
+    let mut network_attempts = 0;
+    let mut server_attempts = 0;
+
+    loop {
+        limiter.wait(url).await;
+
+        let attempt = match client.request(method.clone(), url.clone()).send().await {
+            Ok(res)
+                if (res.status().is_server_error()
+                    || res.status() == StatusCode::TOO_MANY_REQUESTS)
+                    && server_attempts < retries.server =>
+            {
+                server_attempts += 1;
+                server_attempts
+            }
+            Err(err) if is_transient(&err) && network_attempts < retries.network => {
+                network_attempts += 1;
+                network_attempts
+            }
+            result => return result,
+        };
+
+        time::sleep(backoff(retries.base_delay, attempt)).await;
+    }
+}
+
+/// The longest we wait before a retry, however many retries were asked for.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How long to wait before retry number `attempt`, counting from 1: twice as long as the retry
+/// before, up to [`MAX_BACKOFF`].
+fn backoff(base_delay: Duration, attempt: u32) -> Duration {
+    let factor = 2u32.checked_pow(attempt - 1).unwrap_or(u32::MAX);
+    base_delay.saturating_mul(factor).min(MAX_BACKOFF)
+}
+
+/// Describes why fetching a page failed, for the crawl log. reqwest's own message only says which
+/// step failed, so we go by its causes, which tell a DNS failure from a refused connection.
+fn fetch_failure(err: &reqwest::Error) -> String {
+    let mut failure = if err.is_timeout() {
+        "timed out fetching".to_string()
+    } else {
+        "failed to fetch".to_string()
+    };
+
+    let mut source = err.source();
+    while let Some(cause) = source {
+        failure.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+
+    failure
+}
+
+/// Whether a request failed in a way that often works on the next try: DNS lookups and connects
+/// (reqwest counts both as connect errors), timeouts, and connections dropped halfway.
+fn is_transient(err: &reqwest::Error) -> bool {
+    if err.is_connect() || err.is_timeout() {
+        return true;
+    }
+
+    let mut source = err.source();
+    while let Some(e) = source {
+        if let Some(io) = e.downcast_ref::<io::Error>()
+            && matches!(
+                io.kind(),
+                io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+            )
+        {
+            return true;
+        }
+        source = e.source();
+    }
+
+    false
+}
+
+/// What a worker got done over a crawl, to see how the work was spread over the workers.
+#[derive(Debug, Default)]
+pub struct ReportCard {
+    /// Items the worker picked up, whatever came of them.
+    pub items: usize,
+    /// Bytes of response bodies the worker downloaded.
+    pub bytes: u64,
+    /// Items the worker couldn't fetch or extract.
+    pub errors: usize,
+    /// How long the worker was working on items rather than waiting for them.
+    pub busy: Duration,
+}
+
+/// Worker that fetches pages and, if `extract` is set, runs Readability over them. While a page
+/// is being extracted on the blocking pool, the worker already fetches the next one.
+pub async fn spawn_worker(
+    client: Client,
+    inbox: WorkerInbox,
+    robots: Arc<Robots>,
+    limiter: Arc<HostLimiter>,
+    retries: Retries,
+    extract: bool,
+) -> ReportCard {
+    let mut card = ReportCard::default();
+    let started = Instant::now();
+    let mut idle = Duration::ZERO;
+    let mut extracting: Option<JoinHandle<bool>> = None;
+
+    loop {
+        let waiting = Instant::now();
+        let work = inbox.recv().await;
+        idle += waiting.elapsed();
+        let Ok(work) = work else {
+            break;
+        };
+        card.items += 1;
+
+        if !robots.allowed(&work.url).await {
             let _ = work
                 .circle_back
-                .send(Err(anyhow!("failed to fetch {}", work.url)))
+                .send(Ok(Crawled::Disallowed(work.url)))
                 .await;
             continue;
+        }
+
+        // Fetch the website's content.
+        let res = match fetch(&client, Method::GET, &work.url, &limiter, retries).await {
+            Ok(res) => res,
+            Err(err) => {
+                card.errors += 1;
+                let _ = work
+                    .circle_back
+                    .send(Err(CrawlError::new(work.url, fetch_failure(&err))))
+                    .await;
+                continue;
+            }
         };
 
         let status_code = res.status();
+        let final_url = res.url().clone();
+        let content_type = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
 
-        // Decode response as html.
-        let Ok(html) = res.text().await else {
+        // No point in running Readability over PDFs and images.
+        if let Some(content_type) = &content_type
+            && !is_html_content_type(content_type)
+        {
+            card.errors += 1;
             let _ = work
                 .circle_back
-                .send(Err(anyhow!("failed to decode response from {}", work.url)))
+                .send(Err(CrawlError::new(
+                    work.url,
+                    format!("not HTML but {content_type}"),
+                )))
                 .await;
             continue;
-        };
-
-        // Do Readability magic. Needs to be blocking because [`Tendril`]s are !Send.
-        let url2 = work.url.clone();
-        let cfg2 = cfg.clone();
-        let extraction_result = tokio::task::spawn_blocking(move || {
-            let article = Readability::new(html, Some(url2.as_str()), Some(cfg2))
-                .unwrap()
-                .parse()
-                .map_err(|e| anyhow!("failed to parse {}: {e:?}", url2))?;
-
-            Ok(CrawledArticle {
-                timestamp: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("system time > unix epoch")
-                    .as_secs(),
-                status: status_code,
-                url: url2.clone(),
-                html: article.content.to_string(),
-                markdown: article.text_content.to_string(),
-            })
-        })
-        .await;
+        }
 
-        // Send back HTML and extracted markdown content.
-        match extraction_result {
-            Ok(Ok(article)) => {
-                let _ = work.circle_back.send(Ok(article)).await;
-            }
-            Ok(Err(e)) => {
-                let _ = work.circle_back.send(Err(e)).await;
+        // Decode response as html.
+        let html = match res.bytes().await {
+            Ok(body) => {
+                card.bytes += body.len() as u64;
+                decode_html(&body, content_type.as_deref())
             }
-            Err(_) => {
-                // Blocking thread panicked
+            Err(err) => {
+                card.errors += 1;
                 let _ = work
                     .circle_back
-                    .send(Err(anyhow!("dom_smoothie parser panicked on {}", work.url)))
+                    .send(Err(CrawlError::new(work.url, fetch_failure(&err))))
                     .await;
+                continue;
             }
+        };
+
+        let page = RawPage {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("system time > unix epoch")
+                .as_secs(),
+            status: status_code,
+            url: work.url.clone(),
+            final_url,
+            html,
+        };
+
+        if !extract {
+            let _ = work.circle_back.send(Ok(Crawled::Raw(page))).await;
+            continue;
+        }
+
+        // Only one page per worker is extracted at a time, so a slow parse holds up the worker
+        // instead of fetched pages piling up in memory.
+        if let Some(previous) = extracting.take()
+            && !previous.await.unwrap_or(false)
+        {
+            card.errors += 1;
+        }
+        extracting = Some(tokio::spawn(extract_and_send(page, work)));
+    }
+
+    if let Some(previous) = extracting
+        && !previous.await.unwrap_or(false)
+    {
+        card.errors += 1;
+    }
+
+    card.busy = started.elapsed().saturating_sub(idle);
+    card
+}
+
+/// Runs Readability over `page` and sends back the article, or why we couldn't extract one.
+/// Returns whether we got an article.
+async fn extract_and_send(page: RawPage, work: WorkItem) -> bool {
+    // Do Readability magic. Needs to be blocking because [`Tendril`]s are !Send.
+    let extraction_result = tokio::task::spawn_blocking(move || self::extract(page)).await;
+
+    // Send back HTML and extracted markdown content.
+    match extraction_result {
+        Ok(Ok(article)) => {
+            let _ = work.circle_back.send(Ok(Crawled::Article(article))).await;
+            true
+        }
+        Ok(Err(e)) => {
+            let _ = work
+                .circle_back
+                .send(Err(CrawlError::new(work.url, format!("{e:#}"))))
+                .await;
+            false
+        }
+        Err(_) => {
+            // Blocking thread panicked
+            let _ = work
+                .circle_back
+                .send(Err(CrawlError::new(
+                    work.url,
+                    "dom_smoothie parser panicked",
+                )))
+                .await;
+            false
         }
     }
 }
+
+/// Worker that only issues `HEAD` requests, for a cheap look at items before crawling them or to
+/// see whether their links still work. It's as polite and persistent as [`spawn_worker`].
+pub async fn spawn_head_worker(
+    client: Client,
+    inbox: HeadWorkerInbox,
+    robots: Arc<Robots>,
+    limiter: Arc<HostLimiter>,
+    retries: Retries,
+) {
+    while let Ok(work) = inbox.recv().await {
+        if !robots.allowed(&work.url).await {
+            let _ = work
+                .circle_back
+                .send(Err(HeadError {
+                    url: work.url,
+                    cause: HeadFailure::Disallowed,
+                }))
+                .await;
+            continue;
+        }
+
+        let output = match fetch(&client, Method::HEAD, &work.url, &limiter, retries).await {
+            Ok(res) => {
+                let header = |name| {
+                    res.headers()
+                        .get(name)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string)
+                };
+
+                Ok(HeadCheck {
+                    status: res.status(),
+                    url: work.url,
+                    content_type: header(CONTENT_TYPE),
+                    content_length: header(CONTENT_LENGTH).and_then(|v| v.parse().ok()),
+                })
+            }
+            Err(err) => Err(HeadError {
+                url: work.url,
+                cause: HeadFailure::Failed(fetch_failure(&err)),
+            }),
+        };
+
+        let _ = work.circle_back.send(output).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("2024-03-01T12:00:00+01:00"), Some(1709290800));
+        assert_eq!(
+            parse_date("Fri, 01 Mar 2024 11:00:00 GMT"),
+            Some(1709290800)
+        );
+        assert_eq!(parse_date("2024-03-01"), Some(1709251200));
+        assert_eq!(parse_date("2024-03-01T12:00:00"), Some(1709251200));
+        assert_eq!(parse_date("yesterday"), None);
+
+        let html = r#"<p>Posted <time datetime="2024-03-01">March 1st</time></p>"#;
+        assert_eq!(first_time_element(html), Some(1709251200));
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        // A server that is unavailable twice before it comes through.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let server = thread::spawn(move || {
+            for status in [
+                "503 Service Unavailable",
+                "503 Service Unavailable",
+                "200 OK",
+            ] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let _ = stream.read(&mut [0; 1024]).unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+            }
+        });
+
+        let client = Client::builder().no_proxy().build().unwrap();
+        let retries = Retries {
+            network: 0,
+            server: 2,
+            base_delay: Duration::from_millis(1),
+        };
+
+        let res = fetch(&client, Method::GET, &url, &HostLimiter::new(None), retries)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_a_cap() {
+        let base = Duration::from_secs(1);
+        assert_eq!(backoff(base, 1), base);
+        assert_eq!(backoff(base, 3), Duration::from_secs(4));
+        assert_eq!(backoff(base, 20), MAX_BACKOFF);
+        assert_eq!(backoff(base, 33), MAX_BACKOFF);
+        assert_eq!(backoff(base, u32::MAX), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn failures_keep_their_cause() {
+        // Nothing listens on a port we just let go of.
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let url = Url::parse(&format!("http://{addr}/")).unwrap();
+
+        let client = Client::builder().no_proxy().build().unwrap();
+        let retries = Retries {
+            network: 0,
+            server: 0,
+            base_delay: Duration::from_millis(1),
+        };
+        let err = fetch(&client, Method::GET, &url, &HostLimiter::new(None), retries)
+            .await
+            .unwrap_err();
+
+        let failure = fetch_failure(&err);
+        assert!(failure.starts_with("failed to fetch: "), "{failure}");
+        assert!(failure.to_lowercase().contains("refused"), "{failure}");
+    }
+
+    #[test]
+    fn links() {
+        let base = Url::parse("https://example.com/posts/one#top").unwrap();
+        let content = Document::from(
+            r##"<p><a href="/posts/two">Two</a>, <a href="https://other.org/">the
+            other   one</a>, <a href="#footnote">1</a>, <a href="mailto:me@example.com">me</a>,
+            <a href="two">again</a></p>"##,
+        );
+
+        let links = outbound_links(&content, &base);
+        let links: Vec<_> = links
+            .iter()
+            .map(|l| (l.href.as_str(), l.text.as_str()))
+            .collect();
+
+        assert_eq!(
+            links,
+            [
+                ("https://example.com/posts/two", "Two"),
+                ("https://other.org/", "the other one"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unextractable_pages() {
+        let page = |url: &str| RawPage {
+            timestamp: 0,
+            status: StatusCode::OK,
+            url: Url::parse("https://example.com/post").unwrap(),
+            final_url: Url::parse(url).unwrap(),
+            html: "<html><body><p>Unclosed <b><i>tags</p></div>".to_string(),
+        };
+
+        let err = extract(page("data:text/html,<p>hi</p>")).unwrap_err();
+        assert!(format!("{err:#}").contains("https://example.com/post"));
+        assert!(format!("{err:#}").contains("must be absolute"));
+
+        // Broken HTML on its own is fine, the parser repairs it like a browser would.
+        let article = extract(page("https://example.com/post")).unwrap();
+        assert!(article.markdown.contains("Unclosed"));
+    }
+
+    #[test]
+    fn walls() {
+        assert!(looks_walled(
+            "# The big story\n\nThe first paragraph.\n\nSubscribe to continue reading."
+        ));
+        assert!(looks_walled("Please **Sign in to continue**."));
+        assert!(!looks_walled(
+            "# The big story\n\nAll of it, no strings attached."
+        ));
+
+        let long = format!("{}\n\nAlready a subscriber? Log in.", "Words. ".repeat(500));
+        assert!(!looks_walled(&long));
+    }
+
+    #[test]
+    fn charsets() {
+        // "café “quoted”" in Windows-1252.
+        let body = b"<html><head><meta charset=\"windows-1252\"></head><body>caf\xe9 \x93quoted\x94</body></html>";
+        assert!(decode_html(body, None).contains("café “quoted”"));
+        assert!(
+            decode_html(body, Some("text/html; charset=windows-1252")).contains("café “quoted”")
+        );
+
+        let body = "<p>café</p>".as_bytes();
+        assert_eq!(decode_html(body, Some("text/html")), "<p>café</p>");
+        assert_eq!(
+            decode_html(body, Some("text/html; charset=UTF-8")),
+            "<p>café</p>"
+        );
+    }
+
+    #[test]
+    fn html_content_types() {
+        assert!(is_html_content_type("text/html"));
+        assert!(is_html_content_type("text/html; charset=utf-8"));
+        assert!(is_html_content_type("TEXT/HTML;charset=ISO-8859-1"));
+        assert!(is_html_content_type("application/xhtml+xml"));
+        assert!(!is_html_content_type("application/pdf"));
+        assert!(!is_html_content_type("text/plain; charset=utf-8"));
+    }
+}